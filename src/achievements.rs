@@ -0,0 +1,185 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::ValidAccountId;
+use near_sdk::{env, near_bindgen, AccountId};
+use serde::{Serialize, Deserialize};
+
+use crate::nft::TokenMetadata;
+use crate::*;
+
+const DICE_ROLLS_MILESTONE: u64 = 1_000;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Achievement {
+    SicboTriple,
+    RouletteStraightUp,
+    DiceRolls,
+    VipTier(u8),
+}
+
+impl Achievement {
+    fn key(&self) -> String {
+        match self {
+            Achievement::SicboTriple => "sicbo_triple".to_string(),
+            Achievement::RouletteStraightUp => "roulette_straight_up".to_string(),
+            Achievement::DiceRolls => format!("dice_rolls_{}", DICE_ROLLS_MILESTONE),
+            Achievement::VipTier(tier) => format!("vip_tier_{}", tier),
+        }
+    }
+
+    fn title(&self) -> String {
+        match self {
+            Achievement::SicboTriple => "Triple Threat".to_string(),
+            Achievement::RouletteStraightUp => "Straight Up".to_string(),
+            Achievement::DiceRolls => "High Roller".to_string(),
+            Achievement::VipTier(tier) => format!("VIP Tier {}", tier),
+        }
+    }
+
+    fn description(&self) -> String {
+        match self {
+            Achievement::SicboTriple => "Won a triple bet in sic bo".to_string(),
+            Achievement::RouletteStraightUp => "Won a straight-up roulette bet".to_string(),
+            Achievement::DiceRolls => format!("Rolled the dice {} times", DICE_ROLLS_MILESTONE),
+            Achievement::VipTier(tier) => format!("Reached VIP tier {}", tier),
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_achievements(&self, account_id: ValidAccountId) -> Vec<Achievement> {
+        self.achievements.get(account_id.as_ref()).unwrap_or_default()
+    }
+
+    pub fn get_dice_rolls(&self, account_id: ValidAccountId) -> u64 {
+        self.dice_rolls.get(account_id.as_ref()).unwrap_or(0)
+    }
+}
+
+impl Contract {
+    // Each achievement is earned once and minted as a badge NFT to the account
+    pub(crate) fn award_achievement(&mut self, account_id: &AccountId, achievement: Achievement) {
+        let mut earned = self.achievements.get(account_id).unwrap_or_default();
+        if earned.contains(&achievement) {
+            return;
+        }
+        earned.push(achievement);
+        self.achievements.insert(account_id, &earned);
+
+        let metadata = TokenMetadata {
+            title: Some(achievement.title()),
+            description: Some(achievement.description()),
+            issued_at: Some((env::block_timestamp() / 1_000_000).to_string()),
+            ..Default::default()
+        };
+        self.mint_nft(format!("{}:{}", achievement.key(), account_id), account_id.clone(), metadata);
+    }
+
+    // Only a winning `triple_*` or `triple_any` bet counts, not a triple that pays another bet
+    pub(crate) fn check_sicbo_achievements<'a, I: IntoIterator<Item = &'a Bet>>(&mut self, account_id: &AccountId, bets: I, dices: &[u8]) {
+        if bets.into_iter().any(|bet| bet.starts_with("triple_") && check_point_sicbo(bet, dices.to_vec()) > 0) {
+            self.award_achievement(account_id, Achievement::SicboTriple);
+        }
+    }
+
+    // Straight-up bets are keyed by the number alone
+    pub(crate) fn check_roulette_achievements<'a, I: IntoIterator<Item = &'a Bet>>(&mut self, account_id: &AccountId, bets: I, roulette_value: u8) {
+        if bets.into_iter().any(|bet| bet.parse::<u8>().ok() == Some(roulette_value)) {
+            self.award_achievement(account_id, Achievement::RouletteStraightUp);
+        }
+    }
+
+    pub(crate) fn record_dice_roll(&mut self, account_id: &AccountId) {
+        let rolls = self.dice_rolls.get(account_id).unwrap_or(0) + 1;
+        self.dice_rolls.insert(account_id, &rolls);
+        if rolls == DICE_ROLLS_MILESTONE {
+            self.award_achievement(account_id, Achievement::DiceRolls);
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{bets, get_context, set_context};
+    use near_sdk::test_utils::get_logs;
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+    use std::convert::TryInto;
+
+    fn setup() -> Contract {
+        let mut context = get_context(vec![], false);
+        context.attached_deposit = 1000;
+        testing_env!(context);
+        let mut contract = Contract { house: 100_000, ..Default::default() };
+        contract.deposit(None);
+        contract
+    }
+
+    #[test]
+    fn straight_up_win_mints_a_badge_once() {
+        let mut contract = setup();
+        // Under a zero seed nonce 6 spins 0
+        contract.rng_nonce = 6;
+        contract.play_roulette(bets(&[("0", 10), ("red", 10)]), None);
+        contract.rng_nonce = 6;
+        contract.play_roulette(bets(&[("0", 10)]), None);
+
+        assert_eq!(vec![Achievement::RouletteStraightUp], contract.get_achievements("carol_near".try_into().unwrap()));
+        let token = contract.nft_token("roulette_straight_up:carol_near".to_string()).unwrap();
+        assert_eq!("carol_near", token.owner_id);
+        assert_eq!(Some("Straight Up".to_string()), token.metadata.title);
+        let mints = get_logs().iter().filter(|log| log.contains("\"event\":\"nft_mint\"")).count();
+        assert_eq!(1, mints);
+    }
+
+    #[test]
+    fn milestones_and_vip_tiers_are_awarded() {
+        let mut contract = setup();
+        let account_id = "carol_near".to_string();
+        contract.dice_rolls.insert(&account_id, &(DICE_ROLLS_MILESTONE - 1));
+        contract.play_dice(DiceMode::RollUnder, 5000, U128(10), None);
+        let placed = ["big".to_string(), "triple_4".to_string()];
+        contract.check_sicbo_achievements(&account_id, &placed[..1], &[4, 4, 4]);
+        contract.check_sicbo_achievements(&account_id, &placed, &[5, 5, 5]);
+        assert!(!contract.get_achievements(account_id.clone().try_into().unwrap()).contains(&Achievement::SicboTriple));
+        contract.check_sicbo_achievements(&account_id, &placed, &[4, 4, 4]);
+
+        // Wagering past the first threshold moves the account to tier 1
+        let mut account = contract.users.get(&account_id).unwrap();
+        account.wagered = 1_000 * 10u128.pow(24) - 1;
+        contract.users.insert(&account_id, &account);
+        contract.house = 10u128.pow(25);
+        let mut account = contract.users.get(&account_id).unwrap();
+        contract.settle(&mut account, "dice", 1, 0);
+
+        assert_eq!(
+            vec![Achievement::DiceRolls, Achievement::SicboTriple, Achievement::VipTier(1)],
+            contract.get_achievements(account_id.clone().try_into().unwrap())
+        );
+    }
+
+    #[test]
+    fn badges_transfer_between_accounts() {
+        let mut contract = setup();
+        let account_id = "carol_near".to_string();
+        contract.award_achievement(&account_id, Achievement::SicboTriple);
+
+        set_context("carol_near", 0, 0, 1);
+        contract.nft_transfer("dave_near".try_into().unwrap(), "sicbo_triple:carol_near".to_string(), None, None);
+        assert_eq!("dave_near", contract.nft_token("sicbo_triple:carol_near".to_string()).unwrap().owner_id);
+        assert!(get_logs()[0].contains("\"standard\":\"nep171\""));
+        assert_eq!("nft-1.0.0", contract.nft_metadata().spec);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_TOKEN_OWNER")]
+    fn only_the_owner_can_transfer() {
+        let mut contract = setup();
+        contract.award_achievement(&"dave_near".to_string(), Achievement::SicboTriple);
+        set_context("carol_near", 0, 0, 1);
+        contract.nft_transfer("erin_near".try_into().unwrap(), "sicbo_triple:dave_near".to_string(), None, None);
+    }
+}
@@ -0,0 +1,323 @@
+use near_sdk::{env, near_bindgen, AccountId, Balance};
+use near_sdk::json_types::U128;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+use crate::payout::{Multiplier, BASIS_POINTS};
+use crate::*;
+
+pub const DEFAULT_COMMISSION_BPS: u32 = 500;
+
+const PLAYER_RETURN: Multiplier = Multiplier::whole(2);
+const TIE_RETURN: Multiplier = Multiplier::whole(9);
+const PAIR_RETURN: Multiplier = Multiplier::whole(12);
+const PUSH_RETURN: Multiplier = Multiplier::whole(1);
+
+// Player wins, banker wins and ties out of the 13^6 equally likely rank sequences of a deal,
+// as every card is drawn from a fresh deck. A pair comes up once in 13 on either side.
+const OUTCOME_WEIGHTS: [u128; 3] = [2_153_464, 2_212_744, 460_601];
+const PAIR_WEIGHTS: [u128; 2] = [1, 12];
+
+// Cards use the same 0-51 encoding as blackjack
+#[derive(Serialize, Deserialize)]
+pub struct BaccaratResult {
+    account_id: AccountId,
+    player_cards: Vec<u8>,
+    banker_cards: Vec<u8>,
+    total_winning: Balance,
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn play_baccarat(&mut self, bets: HashMap<Bet, U128>) -> BaccaratResult {
+        for (bet, amount) in bets.iter() {
+            assert!(amount.0 <= self.baccarat_max_bet(bet), "ERR_BET_ABOVE_MAX");
+        }
+        let stake = bets.values().fold(0, |total, amount| add_balance(total, amount.0));
+        self.assert_exposure(baccarat_best_case(&bets, self.baccarat_commission_bps), stake);
+        let account_id = env::predecessor_account_id();
+        let mut account = self.users.get(&account_id).expect("Account Id does not exist");
+
+        let (player_cards, banker_cards) = deal_baccarat(|| (self.next_random_number() % 52) as u8);
+        let mut total: u128 = 0;
+        let mut total_winning: u128 = 0;
+        for (bet, balance) in bets {
+            let multiplier = check_point_baccarat(&bet, &player_cards, &banker_cards, self.baccarat_commission_bps);
+            total = add_balance(total, balance.0);
+            total_winning = add_balance(total_winning, multiplier.apply(balance.0));
+        }
+
+        self.settle(&mut account, "baccarat", total, total_winning);
+
+        BaccaratResult {
+            account_id,
+            player_cards,
+            banker_cards,
+            total_winning,
+        }
+    }
+
+    pub fn get_baccarat_commission(&self) -> u32 {
+        self.baccarat_commission_bps
+    }
+
+    pub fn set_baccarat_commission(&mut self, commission_bps: u32) {
+        self.assert_owner();
+        assert!(commission_bps as u128 <= BASIS_POINTS, "ERR_COMMISSION_NOT_VALID");
+        self.baccarat_commission_bps = commission_bps;
+    }
+}
+
+fn banker_return(commission_bps: u32) -> Multiplier {
+    Multiplier::from_bps(2 * BASIS_POINTS - commission_bps as u128)
+}
+
+// Returns of a bet on each outcome with its weight: player win, banker win and tie for the main
+// bets, a pair or no pair for the side bets
+pub(crate) fn baccarat_paytable(bet: &str, commission_bps: u32) -> (Vec<Multiplier>, Vec<u128>) {
+    match bet {
+        "player" => (vec![PLAYER_RETURN, Multiplier::ZERO, PUSH_RETURN], OUTCOME_WEIGHTS.to_vec()),
+        "banker" => (vec![Multiplier::ZERO, banker_return(commission_bps), PUSH_RETURN], OUTCOME_WEIGHTS.to_vec()),
+        "tie" => (vec![Multiplier::ZERO, Multiplier::ZERO, TIE_RETURN], OUTCOME_WEIGHTS.to_vec()),
+        "player_pair" | "banker_pair" => (vec![PAIR_RETURN, Multiplier::ZERO], PAIR_WEIGHTS.to_vec()),
+        _ => env::panic(b"ERR_UNKNOWN_BET"),
+    }
+}
+
+// Pairs can land with any winner, so the best case wins every pair bet on top of the best main outcome
+fn baccarat_best_case(bets: &HashMap<Bet, U128>, commission_bps: u32) -> Balance {
+    let mut main = [0; 3];
+    let mut pairs = 0;
+    for (bet, amount) in bets.iter() {
+        let (paytable, _) = baccarat_paytable(bet, commission_bps);
+        if paytable.len() == main.len() {
+            for (winning, multiplier) in main.iter_mut().zip(paytable.iter()) {
+                *winning = add_balance(*winning, multiplier.apply(amount.0));
+            }
+        } else {
+            pairs = add_balance(pairs, paytable[0].apply(amount.0));
+        }
+    }
+    add_balance(pairs, main.iter().max().cloned().unwrap_or(0))
+}
+
+fn card_point(card: u8) -> u8 {
+    match card % 13 {
+        rank if rank < 9 => rank + 1,
+        _ => 0,
+    }
+}
+
+fn baccarat_total(cards: &[u8]) -> u8 {
+    cards.iter().map(|card| card_point(*card)).sum::<u8>() % 10
+}
+
+// Punto banco tableau: the banker's third card depends on the player's third card
+fn banker_draws(banker_total: u8, player_third_card: Option<u8>) -> bool {
+    match player_third_card.map(card_point) {
+        None => banker_total <= 5,
+        Some(third) => match banker_total {
+            0..=2 => true,
+            3 => third != 8,
+            4 => (2..=7).contains(&third),
+            5 => (4..=7).contains(&third),
+            6 => (6..=7).contains(&third),
+            _ => false,
+        },
+    }
+}
+
+fn deal_baccarat<F: FnMut() -> u8>(mut draw: F) -> (Vec<u8>, Vec<u8>) {
+    let mut player_cards = Vec::new();
+    let mut banker_cards = Vec::new();
+    for _ in 0..2 {
+        player_cards.push(draw());
+        banker_cards.push(draw());
+    }
+
+    let player_total = baccarat_total(&player_cards);
+    let banker_total = baccarat_total(&banker_cards);
+    if player_total >= 8 || banker_total >= 8 {
+        return (player_cards, banker_cards);
+    }
+
+    let mut player_third_card = None;
+    if player_total <= 5 {
+        let card = draw();
+        player_cards.push(card);
+        player_third_card = Some(card);
+    }
+    if banker_draws(banker_total, player_third_card) {
+        banker_cards.push(draw());
+    }
+    (player_cards, banker_cards)
+}
+
+fn is_pair(cards: &[u8]) -> bool {
+    cards[0] % 13 == cards[1] % 13
+}
+
+// Player and banker bets push on a tie
+fn check_point_baccarat(bet: &str, player_cards: &[u8], banker_cards: &[u8], commission_bps: u32) -> Multiplier {
+    let player_total = baccarat_total(player_cards);
+    let banker_total = baccarat_total(banker_cards);
+    match bet {
+        "player" | "banker" if player_total == banker_total => PUSH_RETURN,
+        "player" if player_total > banker_total => PLAYER_RETURN,
+        "banker" if banker_total > player_total => banker_return(commission_bps),
+        "tie" if player_total == banker_total => TIE_RETURN,
+        "player_pair" if is_pair(player_cards) => PAIR_RETURN,
+        "banker_pair" if is_pair(banker_cards) => PAIR_RETURN,
+        "player" | "banker" | "tie" | "player_pair" | "banker_pair" => Multiplier::ZERO,
+        _ => env::panic(b"ERR_UNKNOWN_BET"),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{bets, get_context};
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+
+    const ACE: u8 = 0;
+    const TWO: u8 = 1;
+    const THREE: u8 = 2;
+    const FOUR: u8 = 3;
+    const SIX: u8 = 5;
+    const SEVEN: u8 = 6;
+    const EIGHT: u8 = 7;
+    const NINE: u8 = 8;
+    const TEN: u8 = 9;
+    const KING: u8 = 12;
+
+    fn deal(cards: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let mut cards = cards.iter();
+        deal_baccarat(|| *cards.next().unwrap())
+    }
+
+    #[test]
+    fn naturals_stand() {
+        // Player 8 against banker 3
+        let (player, banker) = deal(&[SIX, ACE, TWO, TWO]);
+        assert_eq!(vec![SIX, TWO], player);
+        assert_eq!(vec![ACE, TWO], banker);
+    }
+
+    #[test]
+    fn third_card_rules() {
+        // Player 5 draws an 8, banker 3 stands on a player eight
+        let (player, banker) = deal(&[FOUR, ACE, ACE, TWO, EIGHT, NINE]);
+        assert_eq!(vec![FOUR, ACE, EIGHT], player);
+        assert_eq!(vec![ACE, TWO], banker);
+
+        // Player 5 draws a 4, banker 6 stands
+        let (player, banker) = deal(&[FOUR, THREE, ACE, THREE, FOUR, NINE]);
+        assert_eq!(3, player.len());
+        assert_eq!(vec![THREE, THREE], banker);
+
+        // Player 5 draws a 7, banker 6 draws
+        let (_, banker) = deal(&[FOUR, THREE, ACE, THREE, SEVEN, NINE]);
+        assert_eq!(vec![THREE, THREE, NINE], banker);
+
+        // Player stands on 7, banker 5 draws
+        let (player, banker) = deal(&[SIX, FOUR, ACE, ACE, KING]);
+        assert_eq!(vec![SIX, ACE], player);
+        assert_eq!(vec![FOUR, ACE, KING], banker);
+
+        // Ten-valued cards count as zero
+        assert_eq!(0, baccarat_total(&[TEN, KING]));
+    }
+
+    #[test]
+    fn payouts() {
+        let player = [NINE, KING];
+        let banker = [SEVEN, SEVEN + 13];
+        assert_eq!(Multiplier::whole(2), check_point_baccarat("player", &player, &banker, 500));
+        assert_eq!(Multiplier::ZERO, check_point_baccarat("banker", &player, &banker, 500));
+        assert_eq!(Multiplier::whole(12), check_point_baccarat("banker_pair", &player, &banker, 500));
+        assert_eq!(Multiplier::from_bps(19_500), check_point_baccarat("banker", &banker, &player, 500));
+        assert_eq!(Multiplier::whole(1), check_point_baccarat("banker", &player, &player, 500));
+        assert_eq!(Multiplier::whole(9), check_point_baccarat("tie", &player, &player, 500));
+    }
+
+    #[test]
+    fn play_baccarat_settles_every_bet() {
+        let mut context = get_context(vec![], false);
+        context.attached_deposit = 1000;
+        testing_env!(context);
+        let mut contract = Contract { house: 1_000_000, ..Default::default() };
+        contract.deposit(None);
+
+        let result = contract.play_baccarat(bets(&[("player", 100), ("banker", 100), ("tie", 10)]));
+        let expected: Balance = [("player", 100), ("banker", 100), ("tie", 10)]
+            .iter()
+            .map(|(bet, amount)| check_point_baccarat(bet, &result.player_cards, &result.banker_cards, 500).apply(*amount))
+            .sum();
+        assert_eq!(expected, result.total_winning);
+        assert_eq!(790 + expected, contract.users.get(&"carol_near".to_string()).unwrap().deposit);
+    }
+
+    #[test]
+    fn outcome_weights_follow_the_tableau() {
+        // Every rank but the ten-valued ones has its own point, so a ten stands for four ranks
+        let ranks: Vec<(u8, u128)> = (ACE..=NINE).map(|rank| (rank, 1)).chain(std::iter::once((TEN, 4))).collect();
+        let mut weights = [0u128; 3];
+        let mut deal_ranks = vec![0; 6];
+        for sequence in 0..10u32.pow(6) {
+            let mut weight = 1;
+            for (index, card) in deal_ranks.iter_mut().enumerate() {
+                let (rank, rank_weight) = ranks[(sequence / 10u32.pow(index as u32) % 10) as usize];
+                *card = rank;
+                weight *= rank_weight;
+            }
+            let (player, banker) = deal(&deal_ranks);
+            let outcome = match baccarat_total(&player).cmp(&baccarat_total(&banker)) {
+                std::cmp::Ordering::Greater => 0,
+                std::cmp::Ordering::Less => 1,
+                std::cmp::Ordering::Equal => 2,
+            };
+            weights[outcome] += weight;
+        }
+        assert_eq!(OUTCOME_WEIGHTS, weights);
+    }
+
+    #[test]
+    fn best_case_counts_one_winner_and_every_pair() {
+        let best_case = baccarat_best_case(&bets(&[("player", 100), ("banker", 100), ("tie", 10), ("banker_pair", 10)]), 500);
+        // A tie pushes both main bets and pays the tie bet
+        assert_eq!(100 + 100 + 90 + 120, best_case);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_BET_ABOVE_MAX")]
+    fn oversized_bets_are_rejected() {
+        let mut context = get_context(vec![], false);
+        context.attached_deposit = 100_000;
+        testing_env!(context);
+        let mut contract = Contract { house: 1_000_000, ..Default::default() };
+        contract.deposit(None);
+        let max_bet = contract.get_baccarat_max_bets(vec!["tie".to_string()])["tie"];
+        contract.play_baccarat(bets(&[("tie", max_bet + 1)]));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_EXPOSURE_TOO_HIGH")]
+    fn exposure_caps_the_combined_best_case() {
+        let mut context = get_context(vec![], false);
+        context.attached_deposit = 100_000;
+        testing_env!(context);
+        let mut contract = Contract { house: 1_000_000, max_exposure_bps: 100, ..Default::default() };
+        contract.deposit(None);
+        contract.play_baccarat(bets(&[("player_pair", 500), ("banker_pair", 500)]));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn commission_is_owner_only() {
+        testing_env!(get_context(vec![], false));
+        let mut contract = Contract::default();
+        contract.set_baccarat_commission(0);
+    }
+}
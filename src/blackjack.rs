@@ -0,0 +1,411 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::ValidAccountId;
+use near_sdk::{env, near_bindgen, AccountId, Balance, BlockHeight};
+use near_sdk::json_types::{U128, U64};
+use serde::{Serialize, Deserialize};
+
+use crate::payout::Multiplier;
+use crate::*;
+
+pub const DEFAULT_TIMEOUT: BlockHeight = 600;
+
+const BLACKJACK_RETURN: Multiplier = Multiplier::from_bps(25_000);
+const WIN_RETURN: Multiplier = Multiplier::whole(2);
+const PUSH_RETURN: Multiplier = Multiplier::whole(1);
+const INSURANCE_RETURN: Multiplier = Multiplier::whole(3);
+// Splitting and doubling both hands stakes four bets and wins back eight, which is more than
+// any natural or insured hand can cost the house
+const WORST_CASE_STAKES: u128 = 4;
+const WORST_CASE_RETURN: Multiplier = Multiplier::whole(8);
+
+// Cards are 0-51, rank is `card % 13` starting from the ace and suit is `card / 13`
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+pub struct BlackjackHand {
+    cards: Vec<u8>,
+    bet: Balance,
+    finished: bool,
+}
+
+// The dealer draws the second card only once every player hand is finished (no hole card),
+// so nothing hidden is ever kept in contract state. `reserved` is the worst case held back
+// from the house until the hand settles.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+pub struct BlackjackGame {
+    hands: Vec<BlackjackHand>,
+    active_hand: u8,
+    dealer_cards: Vec<u8>,
+    insurance: Balance,
+    bonus_stake: Balance,
+    placed_at: U64,
+    reserved: Balance,
+    last_action_block: BlockHeight,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BlackjackResult {
+    account_id: AccountId,
+    game: BlackjackGame,
+    settled: bool,
+    total_winning: Balance,
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn bj_start(&mut self, bet: U128) -> BlackjackResult {
+        assert!(bet.0 > 0, "ERR_ZERO_BET");
+        let account_id = env::predecessor_account_id();
+        if let Some(game) = self.blackjack_games.get(&account_id) {
+            assert!(self.is_blackjack_expired(&game), "ERR_HAND_IN_PROGRESS");
+            self.settle_blackjack(account_id.clone(), game);
+        }
+
+        let worst_case_stake = bet.0.checked_mul(WORST_CASE_STAKES).expect("ERR_BALANCE_OVERFLOW");
+        let reserved = WORST_CASE_RETURN.apply(bet.0);
+        self.assert_exposure(reserved, worst_case_stake);
+        let mut account = self.users.get(&account_id).expect("Account Id does not exist");
+        let bonus_stake = self.collect_stake(&mut account, bet.0);
+        self.users.insert(&account_id, &account);
+        self.reserve_house(reserved);
+
+        let cards = vec![self.draw_card(), self.draw_card()];
+        let game = BlackjackGame {
+            hands: vec![BlackjackHand { finished: hand_value(&cards) == 21, cards, bet: bet.0 }],
+            active_hand: 0,
+            dealer_cards: vec![self.draw_card()],
+            insurance: 0,
+            bonus_stake,
+            placed_at: U64(env::block_timestamp()),
+            reserved,
+            last_action_block: env::block_index(),
+        };
+        self.advance_blackjack(account_id, game)
+    }
+
+    pub fn bj_hit(&mut self) -> BlackjackResult {
+        let (account_id, mut game) = self.active_blackjack_game();
+        let card = self.draw_card();
+        let hand = &mut game.hands[game.active_hand as usize];
+        hand.cards.push(card);
+        hand.finished = hand_value(&hand.cards) >= 21;
+        self.advance_blackjack(account_id, game)
+    }
+
+    pub fn bj_stand(&mut self) -> BlackjackResult {
+        let (account_id, mut game) = self.active_blackjack_game();
+        game.hands[game.active_hand as usize].finished = true;
+        self.advance_blackjack(account_id, game)
+    }
+
+    pub fn bj_double(&mut self) -> BlackjackResult {
+        let (account_id, mut game) = self.active_blackjack_game();
+        let active_hand = game.active_hand as usize;
+        assert_eq!(game.hands[active_hand].cards.len(), 2, "ERR_DOUBLE_NOT_ALLOWED");
+
+        let mut account = self.users.get(&account_id).expect("Account Id does not exist");
+        let bonus_stake = self.collect_stake(&mut account, game.hands[active_hand].bet);
+        self.users.insert(&account_id, &account);
+        game.bonus_stake = add_balance(game.bonus_stake, bonus_stake);
+
+        let card = self.draw_card();
+        let hand = &mut game.hands[active_hand];
+        hand.bet *= 2;
+        hand.cards.push(card);
+        hand.finished = true;
+        self.advance_blackjack(account_id, game)
+    }
+
+    pub fn bj_split(&mut self) -> BlackjackResult {
+        let (account_id, mut game) = self.active_blackjack_game();
+        let cards = &game.hands[0].cards;
+        assert!(
+            game.hands.len() == 1 && cards.len() == 2 && card_value(cards[0]) == card_value(cards[1]),
+            "ERR_SPLIT_NOT_ALLOWED"
+        );
+
+        let bet = game.hands[0].bet;
+        let mut account = self.users.get(&account_id).expect("Account Id does not exist");
+        let bonus_stake = self.collect_stake(&mut account, bet);
+        self.users.insert(&account_id, &account);
+        game.bonus_stake = add_balance(game.bonus_stake, bonus_stake);
+
+        // Split aces receive a single card each
+        let split_aces = cards[0] % 13 == 0;
+        let second_card = game.hands[0].cards.pop().unwrap();
+        game.hands.push(BlackjackHand { cards: vec![second_card], bet, finished: false });
+        for hand_index in 0..2 {
+            let card = self.draw_card();
+            let hand = &mut game.hands[hand_index];
+            hand.cards.push(card);
+            hand.finished = split_aces || hand_value(&hand.cards) == 21;
+        }
+        self.advance_blackjack(account_id, game)
+    }
+
+    pub fn bj_insurance(&mut self) -> BlackjackResult {
+        let (account_id, mut game) = self.active_blackjack_game();
+        assert!(
+            game.dealer_cards[0] % 13 == 0
+                && game.hands.len() == 1
+                && game.hands[0].cards.len() == 2
+                && game.insurance == 0,
+            "ERR_INSURANCE_NOT_ALLOWED"
+        );
+
+        let insurance = game.hands[0].bet / 2;
+        let mut account = self.users.get(&account_id).expect("Account Id does not exist");
+        let bonus_stake = self.collect_stake(&mut account, insurance);
+        self.users.insert(&account_id, &account);
+        game.bonus_stake = add_balance(game.bonus_stake, bonus_stake);
+
+        game.insurance = insurance;
+        self.advance_blackjack(account_id, game)
+    }
+
+    // Anyone can settle a hand that timed out, standing on every unfinished hand
+    pub fn bj_close_expired(&mut self, account_id: ValidAccountId) -> BlackjackResult {
+        let account_id: AccountId = account_id.into();
+        let game = self.blackjack_games.get(&account_id).expect("ERR_NO_ACTIVE_HAND");
+        assert!(self.is_blackjack_expired(&game), "ERR_HAND_NOT_EXPIRED");
+        self.settle_blackjack(account_id, game)
+    }
+
+    pub fn get_blackjack_game(&self, account_id: ValidAccountId) -> Option<BlackjackGame> {
+        self.blackjack_games.get(&account_id.into())
+    }
+
+    pub fn get_blackjack_timeout(&self) -> BlockHeight {
+        self.blackjack_timeout
+    }
+
+    pub fn set_blackjack_timeout(&mut self, blocks: BlockHeight) {
+        self.assert_owner();
+        self.blackjack_timeout = blocks;
+    }
+}
+
+impl Contract {
+    fn draw_card(&mut self) -> u8 {
+        (self.next_random_number() % 52) as u8
+    }
+
+    fn is_blackjack_expired(&self, game: &BlackjackGame) -> bool {
+        match game.last_action_block.checked_add(self.blackjack_timeout) {
+            Some(deadline) => env::block_index() > deadline,
+            None => false,
+        }
+    }
+
+    fn active_blackjack_game(&self) -> (AccountId, BlackjackGame) {
+        let account_id = env::predecessor_account_id();
+        let game = self.blackjack_games.get(&account_id).expect("ERR_NO_ACTIVE_HAND");
+        assert!(!self.is_blackjack_expired(&game), "ERR_HAND_EXPIRED");
+        (account_id, game)
+    }
+
+    fn advance_blackjack(&mut self, account_id: AccountId, mut game: BlackjackGame) -> BlackjackResult {
+        while (game.active_hand as usize) < game.hands.len() && game.hands[game.active_hand as usize].finished {
+            game.active_hand += 1;
+        }
+        if (game.active_hand as usize) == game.hands.len() {
+            return self.settle_blackjack(account_id, game);
+        }
+
+        game.last_action_block = env::block_index();
+        self.blackjack_games.insert(&account_id, &game);
+        BlackjackResult {
+            account_id,
+            game,
+            settled: false,
+            total_winning: 0,
+        }
+    }
+
+    fn settle_blackjack(&mut self, account_id: AccountId, mut game: BlackjackGame) -> BlackjackResult {
+        game.dealer_cards.push(self.draw_card());
+        let dealer_blackjack = is_blackjack(&game.dealer_cards);
+        if !dealer_blackjack && game.hands.iter().any(|hand| hand_value(&hand.cards) <= 21) {
+            // Dealer stands on all 17s
+            while hand_value(&game.dealer_cards) < 17 {
+                game.dealer_cards.push(self.draw_card());
+            }
+        }
+
+        let split = game.hands.len() > 1;
+        let mut total_stake: Balance = game.insurance;
+        let mut total_winning: Balance = 0;
+        for hand in game.hands.iter_mut() {
+            hand.finished = true;
+            total_stake = add_balance(total_stake, hand.bet);
+            let multiplier = hand_multiplier(&hand.cards, split, &game.dealer_cards);
+            total_winning = add_balance(total_winning, multiplier.apply(hand.bet));
+        }
+        if dealer_blackjack {
+            total_winning = add_balance(total_winning, INSURANCE_RETURN.apply(game.insurance));
+        }
+
+        let mut account = self.users.get(&account_id).expect("Account Id does not exist");
+        self.release_house(game.reserved);
+        self.pay_winning(&mut account, "blackjack", total_stake, game.bonus_stake, total_winning, game.placed_at.0, false);
+        self.users.insert(&account_id, &account);
+        self.blackjack_games.remove(&account_id);
+
+        BlackjackResult {
+            account_id,
+            game,
+            settled: true,
+            total_winning,
+        }
+    }
+}
+
+fn card_value(card: u8) -> u8 {
+    match card % 13 {
+        0 => 11,
+        rank if rank < 10 => rank + 1,
+        _ => 10,
+    }
+}
+
+fn hand_value(cards: &[u8]) -> u8 {
+    let mut value: u8 = cards.iter().map(|card| card_value(*card)).sum();
+    let mut soft_aces = cards.iter().filter(|card| *card % 13 == 0).count();
+    while value > 21 && soft_aces > 0 {
+        value -= 10;
+        soft_aces -= 1;
+    }
+    value
+}
+
+fn is_blackjack(cards: &[u8]) -> bool {
+    cards.len() == 2 && hand_value(cards) == 21
+}
+
+// A 21 on split hands counts as a plain 21, and a dealer blackjack beats every other hand
+fn hand_multiplier(cards: &[u8], split: bool, dealer_cards: &[u8]) -> Multiplier {
+    let value = hand_value(cards);
+    let dealer_value = hand_value(dealer_cards);
+    let player_blackjack = !split && is_blackjack(cards);
+    let dealer_blackjack = is_blackjack(dealer_cards);
+
+    if value > 21 {
+        Multiplier::ZERO
+    } else if player_blackjack {
+        if dealer_blackjack { PUSH_RETURN } else { BLACKJACK_RETURN }
+    } else if dealer_blackjack {
+        Multiplier::ZERO
+    } else if dealer_value > 21 || value > dealer_value {
+        WIN_RETURN
+    } else if value == dealer_value {
+        PUSH_RETURN
+    } else {
+        Multiplier::ZERO
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{get_context, set_context};
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+
+    // Ace of spades, king of spades, and so on by rank
+    const ACE: u8 = 0;
+    const FIVE: u8 = 4;
+    const SIX: u8 = 5;
+    const SEVEN: u8 = 6;
+    const NINE: u8 = 8;
+    const KING: u8 = 12;
+
+    fn setup(deposit: Balance) -> Contract {
+        let mut context = get_context(vec![], false);
+        context.attached_deposit = deposit;
+        testing_env!(context);
+        let mut contract = Contract { house: 10u128.pow(25), ..Default::default() };
+        contract.deposit(None);
+        contract
+    }
+
+    #[test]
+    fn hand_values_count_aces_as_one_or_eleven() {
+        assert_eq!(21, hand_value(&[ACE, KING]));
+        assert_eq!(12, hand_value(&[ACE, ACE]));
+        assert_eq!(17, hand_value(&[ACE, FIVE, ACE]));
+        assert_eq!(22, hand_value(&[KING, FIVE, SEVEN]));
+        assert!(is_blackjack(&[KING + 13, ACE + 39]));
+        assert!(!is_blackjack(&[SEVEN, NINE, FIVE]));
+    }
+
+    #[test]
+    fn hand_multipliers() {
+        assert_eq!(BLACKJACK_RETURN, hand_multiplier(&[ACE, KING], false, &[KING, NINE]));
+        assert_eq!(WIN_RETURN, hand_multiplier(&[ACE, KING], true, &[KING, NINE]));
+        assert_eq!(PUSH_RETURN, hand_multiplier(&[ACE, KING], false, &[KING, ACE]));
+        assert_eq!(Multiplier::ZERO, hand_multiplier(&[SEVEN, NINE, FIVE], false, &[KING, ACE]));
+        assert_eq!(WIN_RETURN, hand_multiplier(&[SIX, KING], false, &[SIX, KING, KING]));
+        assert_eq!(Multiplier::ZERO, hand_multiplier(&[SIX, KING, KING], false, &[SIX, KING, KING]));
+        assert_eq!(PUSH_RETURN, hand_multiplier(&[SEVEN, KING], false, &[SEVEN, KING]));
+    }
+
+    #[test]
+    fn stand_settles_through_the_ledger() {
+        let mut contract = setup(1000);
+        let started = contract.bj_start(U128(100));
+        let result = if started.settled { started } else { contract.bj_stand() };
+
+        assert!(result.settled);
+        assert!(contract.blackjack_games.get(&"carol_near".to_string()).is_none());
+        let account = contract.users.get(&"carol_near".to_string()).unwrap();
+        assert_eq!(900 + result.total_winning, account.deposit);
+        assert_eq!(10u128.pow(25) + 100 - result.total_winning, contract.house);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_HAND_EXPIRED")]
+    fn expired_hand_rejects_actions() {
+        let mut contract = setup(1000);
+        contract.blackjack_games.insert(&"carol_near".to_string(), &BlackjackGame {
+            hands: vec![BlackjackHand { cards: vec![SEVEN, NINE], bet: 100, finished: false }],
+            active_hand: 0,
+            dealer_cards: vec![KING],
+            insurance: 0,
+            bonus_stake: 0,
+            placed_at: U64(0),
+            reserved: 0,
+            last_action_block: 0,
+        });
+
+        set_context("carol_near", DEFAULT_TIMEOUT + 1, 0, 0);
+        contract.bj_hit();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_EXPOSURE_TOO_HIGH")]
+    fn start_reserves_for_split_and_double() {
+        // A 30 bet can cost the house 120 after a split and two doubles, over 10% of 1000
+        let mut context = get_context(vec![], false);
+        context.attached_deposit = 1000;
+        testing_env!(context);
+        let mut contract = Contract { house: 1000, ..Default::default() };
+        contract.deposit(None);
+        contract.bj_start(U128(30));
+    }
+
+    #[test]
+    fn huge_timeout_never_expires() {
+        let mut contract = setup(1000);
+        contract.blackjack_timeout = BlockHeight::MAX;
+        let game = BlackjackGame {
+            hands: vec![BlackjackHand { cards: vec![SEVEN, NINE], bet: 100, finished: false }],
+            active_hand: 0,
+            dealer_cards: vec![KING],
+            insurance: 0,
+            bonus_stake: 0,
+            placed_at: U64(0),
+            reserved: 0,
+            last_action_block: 1,
+        };
+        assert!(!contract.is_blackjack_expired(&game));
+    }
+}
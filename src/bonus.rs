@@ -0,0 +1,211 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{U128, ValidAccountId};
+use near_sdk::{near_bindgen, Balance};
+use serde::{Serialize, Deserialize};
+
+use crate::payout::mul_div;
+use crate::*;
+
+const MAX_WAGERING_MULTIPLE: u32 = 100;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BonusOrder {
+    CashFirst,
+    BonusFirst,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+pub struct BonusConfig {
+    wagering_multiple: u32,
+    order: BonusOrder,
+}
+
+impl Default for BonusConfig {
+    fn default() -> Self {
+        Self {
+            wagering_multiple: 20,
+            order: BonusOrder::CashFirst,
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    // The bonus is funded by the owner's share of the house and must be wagered
+    // `wagering_multiple` times before it moves into the withdrawable deposit
+    pub fn grant_bonus(&mut self, account_id: ValidAccountId, amount: U128) -> Account {
+        self.assert_owner();
+        assert!(amount.0 > 0, "ERR_ZERO_BONUS");
+        let mut account = self.get_account(account_id);
+        self.spend_house(amount.0);
+        self.add_bonus(&mut account, amount.0);
+        self.users.insert(&account.account_id, &account);
+        account
+    }
+
+    pub fn get_bonus_config(&self) -> BonusConfig {
+        self.bonus_config.clone()
+    }
+
+    pub fn set_bonus_config(&mut self, config: BonusConfig) {
+        self.assert_owner();
+        assert!(
+            (1..=MAX_WAGERING_MULTIPLE).contains(&config.wagering_multiple),
+            "ERR_WAGERING_MULTIPLE_NOT_VALID"
+        );
+        self.bonus_config = config;
+    }
+}
+
+impl Contract {
+    pub(crate) fn add_bonus(&self, account: &mut Account, amount: Balance) {
+        account.bonus = add_balance(account.bonus, amount);
+        let requirement = amount.checked_mul(self.bonus_config.wagering_multiple as u128).expect("ERR_BONUS_OVERFLOW");
+        account.bonus_wagering = add_balance(account.bonus_wagering, requirement);
+        release_bonus(account);
+    }
+
+    // Takes a stake from the cash and bonus balances in the configured order and returns the
+    // part taken from the bonus
+    pub(crate) fn draw_stake(&self, account: &mut Account, total: Balance) -> Balance {
+        assert!(
+            total <= add_balance(account.deposit, account.bonus),
+            "Account deposit does not sufficient for the bet"
+        );
+
+        let from_bonus = match self.bonus_config.order {
+            BonusOrder::BonusFirst => total.min(account.bonus),
+            BonusOrder::CashFirst => total - total.min(account.deposit),
+        };
+        account.bonus -= from_bonus;
+        account.deposit -= total - from_bonus;
+        from_bonus
+    }
+
+    // Every settled stake counts towards an outstanding wagering requirement
+    pub(crate) fn wager_bonus(&self, account: &mut Account, stake: Balance) {
+        account.bonus_wagering = account.bonus_wagering.saturating_sub(stake);
+        release_bonus(account);
+    }
+
+    // While a wagering requirement is outstanding, the share of the winnings that was staked
+    // from the bonus stays locked with it. The rest is paid as cash.
+    pub(crate) fn credit_winning(&self, account: &mut Account, stake: Balance, bonus_stake: Balance, total_winning: Balance) {
+        let locked = if account.bonus_wagering > 0 && bonus_stake > 0 {
+            mul_div(total_winning, bonus_stake, stake)
+        } else {
+            0
+        };
+        account.bonus = add_balance(account.bonus, locked);
+        account.deposit = add_balance(account.deposit, total_winning - locked);
+    }
+}
+
+fn release_bonus(account: &mut Account) {
+    if account.bonus_wagering == 0 {
+        account.deposit = add_balance(account.deposit, account.bonus);
+        account.bonus = 0;
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::get_context;
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+    use std::convert::TryInto;
+
+    fn setup(order: BonusOrder) -> Contract {
+        let mut context = get_context(vec![], false);
+        context.attached_deposit = 1000;
+        context.predecessor_account_id = "alice_near".to_string();
+        testing_env!(context);
+        let mut contract = Contract { house: 100_000, ..Default::default() };
+        contract.set_bonus_config(BonusConfig { wagering_multiple: 5, order });
+        contract.deposit(None);
+        contract.grant_bonus("alice_near".try_into().unwrap(), U128(100));
+        contract
+    }
+
+    fn account(contract: &Contract) -> Account {
+        contract.users.get(&"alice_near".to_string()).unwrap()
+    }
+
+    #[test]
+    #[should_panic(expected = "Account Id does not exist")]
+    fn bonus_needs_an_account() {
+        testing_env!(get_context(vec![], false));
+        let mut contract = Contract { owner_id: "carol_near".to_string(), house: 1000, ..Default::default() };
+        contract.grant_bonus("dave_near".try_into().unwrap(), U128(100));
+    }
+
+    #[test]
+    fn cash_first_spends_the_deposit() {
+        let mut contract = setup(BonusOrder::CashFirst);
+        let mut player = account(&contract);
+        assert_eq!((1000, 100, 500), (player.deposit, player.bonus, player.bonus_wagering));
+
+        contract.settle(&mut player, "dice", 1050, 0);
+        // Meeting the requirement releases what is left of the bonus
+        assert_eq!((50, 0, 0), (player.deposit, player.bonus, player.bonus_wagering));
+    }
+
+    #[test]
+    fn bonus_first_spends_the_bonus() {
+        let mut contract = setup(BonusOrder::BonusFirst);
+        let mut player = account(&contract);
+        contract.settle(&mut player, "dice", 150, 0);
+        assert_eq!((950, 0, 350), (player.deposit, player.bonus, player.bonus_wagering));
+    }
+
+    #[test]
+    fn bonus_unlocks_once_wagered() {
+        let mut contract = setup(BonusOrder::BonusFirst);
+
+        // A third of the stake came from the bonus, so a third of the winnings stays locked
+        let mut player = account(&contract);
+        contract.settle(&mut player, "dice", 300, 600);
+        let player = account(&contract);
+        assert_eq!((1200, 200, 200), (player.deposit, player.bonus, player.bonus_wagering));
+
+        // The bet that meets the requirement releases the bonus and pays out as cash
+        let mut player = account(&contract);
+        contract.settle(&mut player, "dice", 200, 400);
+        let player = account(&contract);
+        assert_eq!((1600, 0, 0), (player.deposit, player.bonus, player.bonus_wagering));
+    }
+
+    #[test]
+    fn cash_stakes_win_cash() {
+        let mut contract = setup(BonusOrder::CashFirst);
+        let mut player = account(&contract);
+        contract.settle(&mut player, "dice", 300, 600);
+        assert_eq!((1300, 100, 200), (player.deposit, player.bonus, player.bonus_wagering));
+    }
+
+    #[test]
+    fn rounds_closed_at_one_times_do_not_count_as_wagered() {
+        let mut contract = setup(BonusOrder::BonusFirst);
+        let mut player = account(&contract);
+        let bonus_stake = contract.collect_stake(&mut player, 100);
+        contract.pay_winning(&mut player, "hilo", 100, bonus_stake, 100, env::block_timestamp(), true);
+        assert_eq!((1000, 100, 500), (player.deposit, player.bonus, player.bonus_wagering));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_WAGERING_MULTIPLE_NOT_VALID")]
+    fn wagering_multiple_is_bounded() {
+        let mut contract = setup(BonusOrder::CashFirst);
+        contract.set_bonus_config(BonusConfig { wagering_multiple: MAX_WAGERING_MULTIPLE + 1, order: BonusOrder::CashFirst });
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_WITHDRAW_EXCEEDS_CASH")]
+    fn locked_bonus_cannot_be_withdrawn() {
+        let mut contract = setup(BonusOrder::CashFirst);
+        contract.withdraw(1001);
+    }
+}
@@ -0,0 +1,264 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::{env, near_bindgen, AccountId, Balance, BlockHeight};
+use serde::{Serialize, Deserialize};
+
+use crate::payout::BASIS_POINTS;
+use crate::*;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+pub struct CoinFlipConfig {
+    rake_bps: u32,
+    expiry_blocks: BlockHeight,
+}
+
+impl Default for CoinFlipConfig {
+    fn default() -> Self {
+        Self {
+            rake_bps: 200,
+            expiry_blocks: 86_400,
+        }
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CoinSide {
+    Heads,
+    Tails,
+}
+
+// The stake is held here, out of the creator's deposit, until the challenge is settled
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+pub struct CoinFlipChallenge {
+    id: u64,
+    creator: AccountId,
+    side: CoinSide,
+    stake: Balance,
+    expires_at: BlockHeight,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CoinFlipResult {
+    challenge_id: u64,
+    creator: AccountId,
+    acceptor: AccountId,
+    outcome: CoinSide,
+    winner: AccountId,
+    total_winning: Balance,
+    rake: Balance,
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn coinflip_open(&mut self, stake: U128, side: CoinSide) -> CoinFlipChallenge {
+        assert!(stake.0 > 0, "ERR_ZERO_BET");
+        let creator = env::predecessor_account_id();
+        self.check_stake_limits(&creator, stake.0);
+        let mut account = self.users.get(&creator).expect("Account Id does not exist");
+        assert!(stake.0 <= account.deposit, "Account deposit does not sufficient for the bet");
+        account.deposit -= stake.0;
+        self.users.insert(&creator, &account);
+
+        self.coinflip_next_id += 1;
+        let challenge = CoinFlipChallenge {
+            id: self.coinflip_next_id,
+            creator,
+            side,
+            stake: stake.0,
+            // A timeout too large to add never expires
+            expires_at: env::block_index().saturating_add(self.coinflip_config.expiry_blocks),
+        };
+        self.coinflip_challenges.insert(&challenge.id, &challenge);
+        challenge
+    }
+
+    pub fn coinflip_accept(&mut self, challenge_id: u64) -> CoinFlipResult {
+        let challenge = self.coinflip_challenges.get(&challenge_id).expect("ERR_CHALLENGE_NOT_FOUND");
+        let acceptor = env::predecessor_account_id();
+        assert_ne!(acceptor, challenge.creator, "ERR_CANNOT_ACCEPT_OWN_CHALLENGE");
+        assert!(env::block_index() <= challenge.expires_at, "ERR_CHALLENGE_EXPIRED");
+
+        self.check_stake_limits(&acceptor, challenge.stake);
+        let mut account = self.users.get(&acceptor).expect("Account Id does not exist");
+        assert!(challenge.stake <= account.deposit, "Account deposit does not sufficient for the bet");
+        account.deposit -= challenge.stake;
+        self.users.insert(&acceptor, &account);
+        self.coinflip_challenges.remove(&challenge_id);
+
+        let outcome = match self.next_random_number() % 2 {
+            0 => CoinSide::Heads,
+            _ => CoinSide::Tails,
+        };
+        let (winner, loser) = if outcome == challenge.side {
+            (challenge.creator.clone(), acceptor.clone())
+        } else {
+            (acceptor.clone(), challenge.creator.clone())
+        };
+        let pot = add_balance(challenge.stake, challenge.stake);
+        let rake = pot * self.coinflip_config.rake_bps as u128 / BASIS_POINTS;
+        let total_winning = pot - rake;
+
+        let mut winner_account = self.users.get(&winner).expect("Account Id does not exist");
+        winner_account.deposit = add_balance(winner_account.deposit, total_winning);
+        self.users.insert(&winner, &winner_account);
+        self.house = add_balance(self.house, rake);
+        self.record_loss(&winner, challenge.stake, total_winning);
+        self.record_loss(&loser, challenge.stake, 0);
+
+        CoinFlipResult {
+            challenge_id,
+            creator: challenge.creator,
+            acceptor,
+            outcome,
+            winner,
+            total_winning,
+            rake,
+        }
+    }
+
+    pub fn coinflip_cancel(&mut self, challenge_id: u64) -> Balance {
+        let challenge = self.coinflip_challenges.get(&challenge_id).expect("ERR_CHALLENGE_NOT_FOUND");
+        assert_eq!(env::predecessor_account_id(), challenge.creator, "ERR_NOT_CHALLENGE_CREATOR");
+        self.refund_coinflip(challenge)
+    }
+
+    // Anyone can return the stake of an expired challenge to its creator
+    pub fn coinflip_refund_expired(&mut self, challenge_id: u64) -> Balance {
+        let challenge = self.coinflip_challenges.get(&challenge_id).expect("ERR_CHALLENGE_NOT_FOUND");
+        assert!(env::block_index() > challenge.expires_at, "ERR_CHALLENGE_NOT_EXPIRED");
+        self.refund_coinflip(challenge)
+    }
+
+    pub fn get_coinflip_challenge(&self, challenge_id: u64) -> Option<CoinFlipChallenge> {
+        self.coinflip_challenges.get(&challenge_id)
+    }
+
+    // Pages through the open challenges by position, each read is a single lookup
+    pub fn get_coinflip_challenges(&self, from_index: u64, limit: u64) -> Vec<CoinFlipChallenge> {
+        let challenges = self.coinflip_challenges.values_as_vector();
+        (from_index..challenges.len().min(from_index.saturating_add(limit)))
+            .map(|index| challenges.get(index).unwrap())
+            .collect()
+    }
+
+    pub fn get_coinflip_config(&self) -> CoinFlipConfig {
+        self.coinflip_config.clone()
+    }
+
+    pub fn set_coinflip_config(&mut self, config: CoinFlipConfig) {
+        self.assert_owner();
+        assert!((config.rake_bps as u128) < BASIS_POINTS, "ERR_RAKE_NOT_VALID");
+        self.coinflip_config = config;
+    }
+}
+
+impl Contract {
+    fn refund_coinflip(&mut self, challenge: CoinFlipChallenge) -> Balance {
+        let mut account = self.users.get(&challenge.creator).expect("Account Id does not exist");
+        account.deposit = add_balance(account.deposit, challenge.stake);
+        self.users.insert(&challenge.creator, &account);
+        self.coinflip_challenges.remove(&challenge.id);
+        account.deposit
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::limits::{LimitKind, LimitPeriod};
+    use crate::tests::{get_context, set_context};
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+
+    fn setup() -> Contract {
+        testing_env!(get_context(vec![], false));
+        let mut contract = Contract::default();
+        set_context("carol_near", 0, 0, 1000);
+        contract.deposit(None);
+        set_context("dave_near", 0, 0, 1000);
+        contract.deposit(None);
+        contract
+    }
+
+    fn deposit_of(contract: &Contract, account_id: &str) -> Balance {
+        contract.users.get(&account_id.to_string()).unwrap().deposit
+    }
+
+    #[test]
+    fn accept_pays_the_winner_minus_rake() {
+        let mut contract = setup();
+        set_context("carol_near", 0, 0, 0);
+        let challenge = contract.coinflip_open(U128(500), CoinSide::Heads);
+        assert_eq!(500, deposit_of(&contract, "carol_near"));
+        assert_eq!(1, contract.get_coinflip_challenges(0, 10).len());
+
+        set_context("dave_near", 1, 0, 0);
+        let result = contract.coinflip_accept(challenge.id);
+        assert_eq!(20, result.rake);
+        assert_eq!(980, result.total_winning);
+        assert_eq!(20, contract.house);
+        let (winner, loser) = if result.winner == "carol_near" { ("carol_near", "dave_near") } else { ("dave_near", "carol_near") };
+        assert_eq!(result.outcome == CoinSide::Heads, winner == "carol_near");
+        assert_eq!(1480, deposit_of(&contract, winner));
+        assert_eq!(500, deposit_of(&contract, loser));
+        assert!(contract.get_coinflip_challenges(0, 10).is_empty());
+    }
+
+    #[test]
+    fn cancel_and_expiry_refund_the_creator() {
+        let mut contract = setup();
+        set_context("carol_near", 0, 0, 0);
+        let first = contract.coinflip_open(U128(300), CoinSide::Tails);
+        let second = contract.coinflip_open(U128(200), CoinSide::Heads);
+        assert_eq!(500, deposit_of(&contract, "carol_near"));
+        assert_eq!(second.id, contract.get_coinflip_challenges(1, 10)[0].id);
+
+        assert_eq!(800, contract.coinflip_cancel(first.id));
+
+        set_context("dave_near", 86_401, 0, 0);
+        assert_eq!(1000, contract.coinflip_refund_expired(second.id));
+        assert!(contract.get_coinflip_challenge(second.id).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_LOSS_LIMIT_REACHED")]
+    fn lost_stake_counts_towards_the_loss_limit() {
+        let mut contract = setup();
+        for account_id in ["carol_near", "dave_near"].iter() {
+            set_context(account_id, 0, 0, 0);
+            contract.set_gaming_limit(LimitKind::Loss, LimitPeriod::Daily, Some(U128(600)));
+        }
+        set_context("carol_near", 0, 0, 0);
+        let challenge = contract.coinflip_open(U128(500), CoinSide::Heads);
+        set_context("dave_near", 1, 0, 0);
+        let result = contract.coinflip_accept(challenge.id);
+
+        // The winner can still stake up to the limit, the loser only 100 more
+        let loser = if result.winner == "carol_near" { "dave_near" } else { "carol_near" };
+        set_context(&result.winner, 1, 0, 0);
+        contract.coinflip_open(U128(600), CoinSide::Heads);
+        set_context(loser, 1, 0, 0);
+        contract.coinflip_open(U128(101), CoinSide::Heads);
+    }
+
+    #[test]
+    fn huge_expiry_never_expires() {
+        let mut contract = setup();
+        contract.coinflip_config.expiry_blocks = BlockHeight::MAX;
+        set_context("carol_near", 5, 0, 0);
+        let challenge = contract.coinflip_open(U128(300), CoinSide::Tails);
+        assert_eq!(BlockHeight::MAX, challenge.expires_at);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_CHALLENGE_EXPIRED")]
+    fn expired_challenge_cannot_be_accepted() {
+        let mut contract = setup();
+        set_context("carol_near", 0, 0, 0);
+        let challenge = contract.coinflip_open(U128(300), CoinSide::Tails);
+        set_context("dave_near", 86_401, 0, 0);
+        contract.coinflip_accept(challenge.id);
+    }
+}
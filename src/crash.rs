@@ -0,0 +1,346 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{U128, U64, ValidAccountId};
+use near_sdk::{env, near_bindgen, AccountId, Balance, BlockHeight};
+use serde::{Serialize, Deserialize};
+
+use crate::payout::{Multiplier, BASIS_POINTS};
+use crate::*;
+
+const RANDOM_RANGE: u128 = 1 << 32;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+pub struct CrashConfig {
+    pub(crate) house_edge_bps: u32,
+    betting_blocks: BlockHeight,
+    flight_blocks: BlockHeight,
+    growth_bps_per_block: u32,
+}
+
+impl Default for CrashConfig {
+    fn default() -> Self {
+        Self {
+            house_edge_bps: 100,
+            betting_blocks: 30,
+            flight_blocks: 120,
+            growth_bps_per_block: 500,
+        }
+    }
+}
+
+// Bets are taken until `betting_closes`, then the multiplier climbs every block until
+// `flight_ends`. The crash point is only drawn at settlement, after every cash-out is in.
+// `max_winning` is what the round pays if it never crashes, which the exposure cap is held to.
+// Participants are kept in `crash_participants` by join order, so a join only writes its own entry.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+pub struct CrashRound {
+    id: u64,
+    config: CrashConfig,
+    betting_closes: BlockHeight,
+    flight_ends: BlockHeight,
+    participants: u64,
+    crash_point: Option<Multiplier>,
+    settled_participants: u64,
+    total_stake: Balance,
+    total_winning: Balance,
+    max_winning: Balance,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+pub struct CrashBet {
+    bet: Balance,
+    bonus_stake: Balance,
+    placed_at: U64,
+    auto_cashout: Option<Multiplier>,
+    cashout: Option<Multiplier>,
+    total_winning: Option<Balance>,
+}
+
+#[near_bindgen]
+impl Contract {
+    // Joins the round taking bets, opening a new one if the last round has closed
+    pub fn crash_join(&mut self, bet: U128, auto_cashout: Option<Multiplier>) -> CrashRound {
+        assert!(bet.0 > 0, "ERR_ZERO_BET");
+        if let Some(auto_cashout) = auto_cashout {
+            assert!(auto_cashout > Multiplier::whole(1), "ERR_AUTO_CASHOUT_NOT_VALID");
+        }
+        let account_id = env::predecessor_account_id();
+        let mut round = self.open_crash_round();
+        let bet_key = crash_bet_key(round.id, &account_id);
+        assert!(self.crash_bets.get(&bet_key).is_none(), "ERR_ALREADY_JOINED");
+
+        let max_winning = round.max_cashout(auto_cashout).apply(bet.0);
+        round.total_stake = add_balance(round.total_stake, bet.0);
+        round.max_winning = add_balance(round.max_winning, max_winning);
+        self.assert_exposure(round.max_winning, round.total_stake);
+
+        let mut account = self.users.get(&account_id).expect("Account Id does not exist");
+        let bonus_stake = self.collect_stake(&mut account, bet.0);
+        self.users.insert(&account_id, &account);
+        self.reserve_house(max_winning);
+
+        let crash_bet = CrashBet {
+            bet: bet.0,
+            bonus_stake,
+            placed_at: U64(env::block_timestamp()),
+            auto_cashout,
+            cashout: None,
+            total_winning: None,
+        };
+        self.crash_bets.insert(&bet_key, &crash_bet);
+        self.crash_participants.insert(&participant_key(round.id, round.participants), &account_id);
+        round.participants += 1;
+        self.crash_rounds.insert(&round.id, &round);
+        round
+    }
+
+    pub fn crash_cashout(&mut self, round_id: u64) -> Multiplier {
+        let account_id = env::predecessor_account_id();
+        let round = self.crash_rounds.get(&round_id).expect("ERR_ROUND_NOT_FOUND");
+        let block_index = env::block_index();
+        assert!(
+            block_index > round.betting_closes && block_index <= round.flight_ends,
+            "ERR_ROUND_NOT_IN_FLIGHT"
+        );
+
+        let bet_key = crash_bet_key(round_id, &account_id);
+        let mut crash_bet = self.crash_bets.get(&bet_key).expect("ERR_NOT_JOINED");
+        assert!(crash_bet.cashout.is_none(), "ERR_ALREADY_CASHED_OUT");
+
+        // An auto cash-out that was reached earlier takes precedence
+        let current = round.multiplier_at(block_index);
+        let cashout = match crash_bet.auto_cashout {
+            Some(auto_cashout) if auto_cashout <= current => auto_cashout,
+            _ => current,
+        };
+        crash_bet.cashout = Some(cashout);
+        self.crash_bets.insert(&bet_key, &crash_bet);
+        cashout
+    }
+
+    // Draws the crash point on the first call and settles up to `limit` participants per call
+    pub fn crash_settle(&mut self, round_id: u64, limit: u64) -> CrashRound {
+        let mut round = self.crash_rounds.get(&round_id).expect("ERR_ROUND_NOT_FOUND");
+        assert!(env::block_index() > round.flight_ends, "ERR_ROUND_NOT_FINISHED");
+        assert!(round.settled_participants < round.participants, "ERR_ROUND_ALREADY_SETTLED");
+
+        let crash_point = match round.crash_point {
+            Some(crash_point) => crash_point,
+            None => {
+                let crash_point = crash_point(self.next_random_number(), round.config.house_edge_bps);
+                round.crash_point = Some(crash_point);
+                crash_point
+            }
+        };
+
+        let end = round.settled_participants.saturating_add(limit).min(round.participants);
+        for index in round.settled_participants..end {
+            let account_id = self.crash_participants.get(&participant_key(round_id, index)).unwrap();
+            let bet_key = crash_bet_key(round_id, &account_id);
+            let mut crash_bet = self.crash_bets.get(&bet_key).unwrap();
+
+            // Players still in the air when the flight window ends cash out at its final multiplier
+            let max_cashout = round.max_cashout(crash_bet.auto_cashout);
+            let cashout = crash_bet.cashout.unwrap_or(max_cashout);
+            let total_winning = if cashout <= crash_point { cashout.apply(crash_bet.bet) } else { 0 };
+
+            let mut account = self.users.get(&account_id).expect("Account Id does not exist");
+            self.release_house(max_cashout.apply(crash_bet.bet));
+            self.pay_winning(&mut account, "crash", crash_bet.bet, crash_bet.bonus_stake, total_winning, crash_bet.placed_at.0, false);
+            self.users.insert(&account_id, &account);
+
+            crash_bet.cashout = Some(cashout);
+            crash_bet.total_winning = Some(total_winning);
+            self.crash_bets.insert(&bet_key, &crash_bet);
+            round.total_winning = add_balance(round.total_winning, total_winning);
+        }
+        round.settled_participants = end;
+        self.crash_rounds.insert(&round_id, &round);
+        round
+    }
+
+    pub fn get_crash_round(&self, round_id: u64) -> Option<CrashRound> {
+        self.crash_rounds.get(&round_id)
+    }
+
+    pub fn get_crash_round_id(&self) -> u64 {
+        self.crash_round_id
+    }
+
+    pub fn get_crash_participants(&self, round_id: u64, from_index: u64, limit: u64) -> Vec<AccountId> {
+        let round = self.crash_rounds.get(&round_id).expect("ERR_ROUND_NOT_FOUND");
+        (from_index..round.participants.min(from_index.saturating_add(limit)))
+            .map(|index| self.crash_participants.get(&participant_key(round_id, index)).unwrap())
+            .collect()
+    }
+
+    pub fn get_crash_bet(&self, round_id: u64, account_id: ValidAccountId) -> Option<CrashBet> {
+        self.crash_bets.get(&crash_bet_key(round_id, account_id.as_ref()))
+    }
+
+    pub fn get_crash_config(&self) -> CrashConfig {
+        self.crash_config.clone()
+    }
+
+    pub fn set_crash_config(&mut self, config: CrashConfig) {
+        self.assert_owner();
+        assert!((config.house_edge_bps as u128) < BASIS_POINTS, "ERR_HOUSE_EDGE_NOT_VALID");
+        assert!(config.flight_blocks > 0 && config.growth_bps_per_block > 0, "ERR_CRASH_CONFIG_NOT_VALID");
+        self.crash_config = config;
+    }
+}
+
+impl Contract {
+    fn open_crash_round(&mut self) -> CrashRound {
+        let block_index = env::block_index();
+        if let Some(round) = self.crash_rounds.get(&self.crash_round_id) {
+            if block_index <= round.betting_closes {
+                return round;
+            }
+        }
+
+        self.crash_round_id += 1;
+        let betting_closes = block_index + self.crash_config.betting_blocks;
+        CrashRound {
+            id: self.crash_round_id,
+            config: self.crash_config.clone(),
+            betting_closes,
+            flight_ends: betting_closes + self.crash_config.flight_blocks,
+            participants: 0,
+            crash_point: None,
+            settled_participants: 0,
+            total_stake: 0,
+            total_winning: 0,
+            max_winning: 0,
+        }
+    }
+}
+
+impl CrashRound {
+    fn multiplier_at(&self, block_index: BlockHeight) -> Multiplier {
+        let elapsed = block_index.min(self.flight_ends).saturating_sub(self.betting_closes);
+        Multiplier::from_bps(BASIS_POINTS + self.config.growth_bps_per_block as u128 * elapsed as u128)
+    }
+
+    // No bet can cash out above the multiplier reached when the flight window ends
+    fn max_cashout(&self, auto_cashout: Option<Multiplier>) -> Multiplier {
+        let final_multiplier = self.multiplier_at(self.flight_ends);
+        match auto_cashout {
+            Some(auto_cashout) if auto_cashout < final_multiplier => auto_cashout,
+            _ => final_multiplier,
+        }
+    }
+}
+
+fn crash_bet_key(round_id: u64, account_id: &str) -> String {
+    format!("{}:{}", round_id, account_id)
+}
+
+// Round participants by join order, which paginated settlement walks through
+pub(crate) fn participant_key(round_id: u64, index: u64) -> String {
+    format!("{}:{}", round_id, index)
+}
+
+// The chance of reaching a multiplier `m` is (1 - house edge) / m
+fn crash_point(random_number: u32, house_edge_bps: u32) -> Multiplier {
+    let numerator = (BASIS_POINTS - house_edge_bps as u128) * RANDOM_RANGE;
+    Multiplier::from_bps(numerator / (RANDOM_RANGE - random_number as u128))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{get_context, set_context};
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn crash_point_distribution_bounds() {
+        assert_eq!(Multiplier::from_bps(9_900), crash_point(0, 100));
+        assert_eq!(Multiplier::from_bps(19_800), crash_point(1 << 31, 100));
+        assert_eq!(Multiplier::from_bps(9_900 * RANDOM_RANGE), crash_point(u32::MAX, 100));
+    }
+
+    #[test]
+    fn round_lifecycle() {
+        testing_env!(get_context(vec![], false));
+        set_context("carol_near", 0, 0, 1000);
+        let mut contract = Contract { house: 100_000, ..Default::default() };
+        contract.deposit(None);
+        set_context("dave_near", 0, 0, 1000);
+        contract.deposit(None);
+
+        contract.crash_join(U128(100), None);
+        set_context("carol_near", 10, 0, 0);
+        let round = contract.crash_join(U128(100), Some(Multiplier::from_bps(12_000)));
+        assert_eq!(1, round.id);
+        assert_eq!(2, round.participants);
+        assert_eq!(vec!["dave_near".to_string()], contract.get_crash_participants(1, 0, 1));
+
+        // Two blocks into the flight at 0.05x per block
+        set_context("dave_near", 32, 0, 0);
+        assert_eq!(Multiplier::from_bps(11_000), contract.crash_cashout(1));
+
+        set_context("carol_near", 151, 0, 0);
+        let round = contract.crash_settle(1, 1);
+        assert_eq!(1, round.settled_participants);
+        let round = contract.crash_settle(1, 10);
+        assert_eq!(2, round.settled_participants);
+
+        let crash_point = round.crash_point.unwrap();
+        for (account_id, cashout) in [("dave_near", 11_000), ("carol_near", 12_000)].iter() {
+            let cashout = Multiplier::from_bps(*cashout);
+            let expected = if cashout <= crash_point { cashout.apply(100) } else { 0 };
+            let crash_bet = contract.crash_bets.get(&crash_bet_key(1, account_id)).unwrap();
+            assert_eq!(Some(expected), crash_bet.total_winning);
+            assert_eq!(900 + expected, contract.users.get(&account_id.to_string()).unwrap().deposit);
+        }
+
+        // A join after betting closes opens the next round
+        let round = contract.crash_join(U128(100), None);
+        assert_eq!(2, round.id);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ROUND_NOT_IN_FLIGHT")]
+    fn cashout_during_betting_fails() {
+        testing_env!(get_context(vec![], false));
+        set_context("carol_near", 0, 0, 1000);
+        let mut contract = Contract { house: 100_000, ..Default::default() };
+        contract.deposit(None);
+        contract.crash_join(U128(100), None);
+        contract.crash_cashout(1);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_EXPOSURE_TOO_HIGH")]
+    fn round_exposure_is_capped() {
+        // The flight tops out at 7x, so two 100 bets can cost 1200 against a 1000 limit
+        testing_env!(get_context(vec![], false));
+        set_context("carol_near", 0, 0, 1000);
+        let mut contract = Contract { house: 10_000, ..Default::default() };
+        contract.deposit(None);
+        set_context("dave_near", 0, 0, 1000);
+        contract.deposit(None);
+
+        let round = contract.crash_join(U128(100), None);
+        assert_eq!(700, round.max_winning);
+        set_context("carol_near", 1, 0, 0);
+        contract.crash_join(U128(100), None);
+    }
+
+    #[test]
+    fn auto_cashout_lowers_the_round_exposure() {
+        testing_env!(get_context(vec![], false));
+        set_context("carol_near", 0, 0, 1000);
+        let mut contract = Contract { house: 10_000, ..Default::default() };
+        contract.deposit(None);
+        set_context("dave_near", 0, 0, 1000);
+        contract.deposit(None);
+
+        contract.crash_join(U128(100), None);
+        set_context("carol_near", 1, 0, 0);
+        let round = contract.crash_join(U128(100), Some(Multiplier::whole(2)));
+        assert_eq!(900, round.max_winning);
+    }
+}
@@ -0,0 +1,24 @@
+use near_sdk::env;
+use serde::Serialize;
+
+const EVENT_STANDARD: &str = "sicbo";
+const EVENT_VERSION: &str = "1.0.0";
+
+#[derive(Serialize)]
+struct EventLog<'a, T: Serialize> {
+    standard: &'a str,
+    version: &'a str,
+    event: &'a str,
+    data: T,
+}
+
+// Logs an NEP-297 event so indexers can follow results without replaying calls
+pub(crate) fn emit_event<T: Serialize>(event: &str, data: T) {
+    emit_standard_event(EVENT_STANDARD, EVENT_VERSION, event, data);
+}
+
+pub(crate) fn emit_standard_event<T: Serialize>(standard: &str, version: &str, event: &str, data: T) {
+    let log = EventLog { standard, version, event, data };
+    let json = serde_json::to_string(&log).expect("ERR_EVENT_NOT_SERIALIZABLE");
+    env::log(format!("EVENT_JSON:{}", json).as_bytes());
+}
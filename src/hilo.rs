@@ -0,0 +1,254 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{U128, U64, ValidAccountId};
+use near_sdk::{env, near_bindgen, AccountId, Balance, BlockHeight};
+use serde::{Serialize, Deserialize};
+
+use crate::payout::{Multiplier, BASIS_POINTS};
+use crate::*;
+
+const RANKS: u8 = 13;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+pub struct HiLoConfig {
+    pub(crate) house_edge_bps: u32,
+    timeout_blocks: BlockHeight,
+}
+
+impl Default for HiLoConfig {
+    fn default() -> Self {
+        Self {
+            house_edge_bps: 100,
+            timeout_blocks: 600,
+        }
+    }
+}
+
+// Aces are low, and a card of the same rank wins either guess
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum HiLoGuess {
+    Higher,
+    Lower,
+}
+
+// Cards use the same 0-51 encoding as blackjack, the last one is face up. The house keeps the
+// current cash-out value reserved while the session is open.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+pub struct HiLoSession {
+    bet: Balance,
+    bonus_stake: Balance,
+    placed_at: U64,
+    cards: Vec<u8>,
+    multiplier: Multiplier,
+    last_action_block: BlockHeight,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct HiLoResult {
+    account_id: AccountId,
+    session: HiLoSession,
+    active: bool,
+    total_winning: Balance,
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn hilo_start(&mut self, bet: U128) -> HiLoResult {
+        assert!(bet.0 > 0, "ERR_ZERO_BET");
+        let account_id = env::predecessor_account_id();
+        if let Some(session) = self.hilo_sessions.get(&account_id) {
+            assert!(self.is_hilo_expired(&session), "ERR_SESSION_IN_PROGRESS");
+            self.cashout_hilo(account_id.clone(), session);
+        }
+
+        let mut account = self.users.get(&account_id).expect("Account Id does not exist");
+        let bonus_stake = self.collect_stake(&mut account, bet.0);
+        self.users.insert(&account_id, &account);
+        self.reserve_house(bet.0);
+
+        let session = HiLoSession {
+            bet: bet.0,
+            bonus_stake,
+            placed_at: U64(env::block_timestamp()),
+            cards: vec![self.draw_hilo_card()],
+            multiplier: Multiplier::whole(1),
+            last_action_block: env::block_index(),
+        };
+        self.hilo_sessions.insert(&account_id, &session);
+        HiLoResult { account_id, session, active: true, total_winning: 0 }
+    }
+
+    pub fn hilo_guess(&mut self, guess: HiLoGuess) -> HiLoResult {
+        let (account_id, mut session) = self.active_hilo_session();
+        let rank = session.cards.last().unwrap() % RANKS;
+        let step = hilo_step_multiplier(rank, guess, self.hilo_config.house_edge_bps);
+        let multiplier = session.multiplier.times(step);
+        self.assert_exposure(multiplier.apply(session.bet), session.bet);
+
+        let card = self.draw_hilo_card();
+        session.cards.push(card);
+        let won = match guess {
+            HiLoGuess::Higher => card % RANKS >= rank,
+            HiLoGuess::Lower => card % RANKS <= rank,
+        };
+        self.release_house(session.multiplier.apply(session.bet));
+        if !won {
+            session.multiplier = Multiplier::ZERO;
+            return self.cashout_hilo(account_id, session);
+        }
+
+        session.multiplier = multiplier;
+        self.reserve_house(multiplier.apply(session.bet));
+        session.last_action_block = env::block_index();
+        self.hilo_sessions.insert(&account_id, &session);
+        HiLoResult { account_id, session, active: true, total_winning: 0 }
+    }
+
+    // At least one guess must be made, so a session cannot be opened and closed at 1x for free
+    pub fn hilo_cashout(&mut self) -> HiLoResult {
+        let (account_id, session) = self.active_hilo_session();
+        assert!(session.cards.len() > 1, "ERR_NO_GUESS");
+        self.cashout_hilo(account_id, session)
+    }
+
+    // Anyone can close a timed out session, which cashes it out at its current multiplier
+    pub fn hilo_close_expired(&mut self, account_id: ValidAccountId) -> HiLoResult {
+        let account_id: AccountId = account_id.into();
+        let session = self.hilo_sessions.get(&account_id).expect("ERR_NO_ACTIVE_SESSION");
+        assert!(self.is_hilo_expired(&session), "ERR_SESSION_NOT_EXPIRED");
+        self.cashout_hilo(account_id, session)
+    }
+
+    pub fn get_hilo_session(&self, account_id: ValidAccountId) -> Option<HiLoSession> {
+        self.hilo_sessions.get(&account_id.into())
+    }
+
+    pub fn get_hilo_config(&self) -> HiLoConfig {
+        self.hilo_config.clone()
+    }
+
+    pub fn set_hilo_config(&mut self, config: HiLoConfig) {
+        self.assert_owner();
+        assert!((config.house_edge_bps as u128) < BASIS_POINTS, "ERR_HOUSE_EDGE_NOT_VALID");
+        self.hilo_config = config;
+    }
+}
+
+impl Contract {
+    fn draw_hilo_card(&mut self) -> u8 {
+        (self.next_random_number() % 52) as u8
+    }
+
+    fn is_hilo_expired(&self, session: &HiLoSession) -> bool {
+        match session.last_action_block.checked_add(self.hilo_config.timeout_blocks) {
+            Some(deadline) => env::block_index() > deadline,
+            None => false,
+        }
+    }
+
+    fn active_hilo_session(&self) -> (AccountId, HiLoSession) {
+        let account_id = env::predecessor_account_id();
+        let session = self.hilo_sessions.get(&account_id).expect("ERR_NO_ACTIVE_SESSION");
+        assert!(!self.is_hilo_expired(&session), "ERR_SESSION_EXPIRED");
+        (account_id, session)
+    }
+
+    fn cashout_hilo(&mut self, account_id: AccountId, session: HiLoSession) -> HiLoResult {
+        let total_winning = session.multiplier.apply(session.bet);
+        self.release_house(total_winning);
+        let mut account = self.users.get(&account_id).expect("Account Id does not exist");
+        let void = session.cards.len() == 1;
+        self.pay_winning(&mut account, "hilo", session.bet, session.bonus_stake, total_winning, session.placed_at.0, void);
+        self.users.insert(&account_id, &account);
+        self.hilo_sessions.remove(&account_id);
+        HiLoResult { account_id, session, active: false, total_winning }
+    }
+}
+
+// The return after the house edge over the exact chance of the next card winning the guess
+fn hilo_step_multiplier(rank: u8, guess: HiLoGuess, house_edge_bps: u32) -> Multiplier {
+    let winning_ranks = match guess {
+        HiLoGuess::Higher => RANKS - rank,
+        HiLoGuess::Lower => rank + 1,
+    };
+    Multiplier::from_bps(BASIS_POINTS - house_edge_bps as u128).over_probability(winning_ranks as u128, RANKS as u128)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{get_context, set_context};
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+    use std::convert::TryInto;
+
+    fn setup() -> Contract {
+        let mut context = get_context(vec![], false);
+        context.attached_deposit = 1000;
+        testing_env!(context);
+        let mut contract = Contract { house: 1_000_000, ..Default::default() };
+        contract.deposit(None);
+        contract
+    }
+
+    #[test]
+    fn step_multipliers_use_exact_probabilities() {
+        // A seven wins higher or lower with 7 of 13 ranks
+        assert_eq!(Multiplier::from_bps(18_385), hilo_step_multiplier(6, HiLoGuess::Higher, 100));
+        assert_eq!(Multiplier::from_bps(18_385), hilo_step_multiplier(6, HiLoGuess::Lower, 100));
+        // Only another king beats a king
+        assert_eq!(Multiplier::from_bps(128_700), hilo_step_multiplier(12, HiLoGuess::Higher, 100));
+        assert_eq!(Multiplier::from_bps(9_900), hilo_step_multiplier(12, HiLoGuess::Lower, 100));
+        assert_eq!(Multiplier::from_bps(33_798), Multiplier::from_bps(18_385).times(Multiplier::from_bps(18_384)));
+    }
+
+    #[test]
+    fn guess_then_cashout() {
+        let mut contract = setup();
+        let started = contract.hilo_start(U128(100));
+        let rank = started.session.cards[0] % RANKS;
+        let guess = if rank < 6 { HiLoGuess::Higher } else { HiLoGuess::Lower };
+
+        let result = contract.hilo_guess(guess);
+        let account_id = "carol_near".to_string();
+        if result.active {
+            assert_eq!(hilo_step_multiplier(rank, guess, 100), result.session.multiplier);
+            let result = contract.hilo_cashout();
+            assert_eq!(result.session.multiplier.apply(100), result.total_winning);
+            assert_eq!(900 + result.total_winning, contract.users.get(&account_id).unwrap().deposit);
+        } else {
+            assert_eq!(900, contract.users.get(&account_id).unwrap().deposit);
+        }
+        assert!(contract.hilo_sessions.get(&account_id).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NO_GUESS")]
+    fn cashout_needs_a_guess() {
+        let mut contract = setup();
+        contract.hilo_start(U128(100));
+        contract.hilo_cashout();
+    }
+
+    #[test]
+    fn expired_session_is_cashed_out() {
+        let mut contract = setup();
+        contract.hilo_start(U128(100));
+
+        set_context("dave_near", 601, 0, 0);
+        let result = contract.hilo_close_expired("carol_near".to_string().try_into().unwrap());
+        assert_eq!(100, result.total_winning);
+        assert_eq!(1000, contract.users.get(&"carol_near".to_string()).unwrap().deposit);
+    }
+
+    #[test]
+    fn huge_timeout_never_expires() {
+        let mut contract = setup();
+        contract.hilo_config.timeout_blocks = BlockHeight::MAX;
+        contract.hilo_start(U128(100));
+        let mut session = contract.hilo_sessions.get(&"carol_near".to_string()).unwrap();
+        session.last_action_block = 1;
+        assert!(!contract.is_hilo_expired(&session));
+    }
+}
@@ -0,0 +1,207 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::{env, near_bindgen, AccountId, Balance, BlockHeight};
+use serde::{Serialize, Deserialize};
+
+use crate::events::emit_event;
+use crate::payout::BASIS_POINTS;
+use crate::*;
+
+// A slice of every sic bo, roulette and dice stake is moved from the house into the pool,
+// which is paid out in full on the first rare event. Only bets of at least `min_stake` in
+// total can hit it, so the pool cannot be farmed with dust bets.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+pub struct JackpotConfig {
+    contribution_bps: u32,
+    min_stake: U128,
+    sicbo_triple: u8,
+    roulette_zero_streak: u8,
+    dice_value: u16,
+}
+
+impl Default for JackpotConfig {
+    fn default() -> Self {
+        Self {
+            contribution_bps: 100,
+            min_stake: U128(100_000_000_000_000_000_000_000),
+            sicbo_triple: 6,
+            roulette_zero_streak: 3,
+            dice_value: 7_777,
+        }
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+pub struct JackpotWin {
+    account_id: AccountId,
+    game: String,
+    amount: Balance,
+    block_index: BlockHeight,
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_jackpot(&self) -> Balance {
+        self.jackpot
+    }
+
+    pub fn get_jackpot_history(&self, from_index: u64, limit: u64) -> Vec<JackpotWin> {
+        (from_index..self.jackpot_history.len().min(from_index.saturating_add(limit)))
+            .map(|index| self.jackpot_history.get(index).unwrap())
+            .collect()
+    }
+
+    pub fn get_roulette_zero_streak(&self, account_id: ValidAccountId) -> u8 {
+        self.roulette_zero_streaks.get(account_id.as_ref()).unwrap_or(0)
+    }
+
+    pub fn get_jackpot_config(&self) -> JackpotConfig {
+        self.jackpot_config.clone()
+    }
+
+    pub fn set_jackpot_config(&mut self, config: JackpotConfig) {
+        self.assert_owner();
+        assert!((config.contribution_bps as u128) < BASIS_POINTS, "ERR_CONTRIBUTION_NOT_VALID");
+        assert!((1..=6).contains(&config.sicbo_triple), "ERR_JACKPOT_TRIPLE_NOT_VALID");
+        assert!(config.roulette_zero_streak > 0, "ERR_JACKPOT_STREAK_NOT_VALID");
+        assert!((config.dice_value as u32) < DICE_OUTCOMES, "ERR_JACKPOT_DICE_NOT_VALID");
+        self.jackpot_config = config;
+    }
+}
+
+impl Contract {
+    // Funds the pool from a settled stake and pays it out to the account if the rare event hit
+    fn contribute_jackpot(&mut self, account: &mut Account, total: Balance, game: &str, hit: bool) -> Balance {
+        let contribution = total * self.jackpot_config.contribution_bps as u128 / BASIS_POINTS;
+        self.house = self.house.checked_sub(contribution).expect("ERR_HOUSE_BALANCE_NOT_SUFFICIENT");
+        self.jackpot = add_balance(self.jackpot, contribution);
+        if !hit || self.jackpot == 0 {
+            return 0;
+        }
+
+        let win = JackpotWin {
+            account_id: account.account_id.clone(),
+            game: game.to_string(),
+            amount: self.jackpot,
+            block_index: env::block_index(),
+        };
+        account.deposit = add_balance(account.deposit, win.amount);
+        self.users.insert(&account.account_id, account);
+        self.jackpot = 0;
+        self.jackpot_history.push(&win);
+        emit_event("jackpot_win", vec![win.clone()]);
+        win.amount
+    }
+
+    pub(crate) fn sicbo_jackpot_hit(&self, dices: &[u8], total: Balance) -> bool {
+        total >= self.jackpot_config.min_stake.0 && dices.iter().all(|dice| *dice == self.jackpot_config.sicbo_triple)
+    }
+
+    // Tracks consecutive zeros per player, across blocks and calls. A spin below the minimum
+    // stake breaks the streak.
+    pub(crate) fn roulette_jackpot_hit(&mut self, account_id: &AccountId, roulette_value: u8, total: Balance) -> bool {
+        if roulette_value != 0 || total < self.jackpot_config.min_stake.0 {
+            self.roulette_zero_streaks.remove(account_id);
+            return false;
+        }
+        let streak = self.roulette_zero_streaks.get(account_id).unwrap_or(0) + 1;
+        if streak >= self.jackpot_config.roulette_zero_streak {
+            self.roulette_zero_streaks.remove(account_id);
+            return true;
+        }
+        self.roulette_zero_streaks.insert(account_id, &streak);
+        false
+    }
+
+    pub(crate) fn dice_jackpot_hit(&self, dice_value: u16, total: Balance) -> bool {
+        total >= self.jackpot_config.min_stake.0 && dice_value == self.jackpot_config.dice_value
+    }
+
+    pub(crate) fn settle_with_jackpot(
+        &mut self,
+        account: &mut Account,
+        total: Balance,
+        total_winning: Balance,
+        game: &str,
+        hit: bool,
+    ) -> Balance {
+        self.settle(account, game, total, total_winning);
+        self.contribute_jackpot(account, total, game, hit)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{bets, get_context};
+    use near_sdk::test_utils::get_logs;
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+
+    fn setup() -> Contract {
+        let mut context = get_context(vec![], false);
+        context.attached_deposit = 10_000;
+        testing_env!(context);
+        let mut contract = Contract { house: 100_000, ..Default::default() };
+        contract.jackpot_config.min_stake = U128(100);
+        contract.deposit(None);
+        contract
+    }
+
+    #[test]
+    fn every_stake_funds_the_pool() {
+        let mut contract = setup();
+        let result = contract.play_sicbo(bets(&[("small", 1000), ("big", 1000)]), None);
+        contract.play_dice(DiceMode::RollUnder, 5000, U128(500), None);
+        assert_eq!(25, contract.get_jackpot());
+        assert_eq!(0, result.jackpot);
+        let deposit = contract.users.get(&"carol_near".to_string()).unwrap().deposit;
+        assert_eq!(110_000, contract.house + contract.jackpot + deposit);
+    }
+
+    #[test]
+    fn roulette_zero_streak_wins_the_pool() {
+        let mut contract = setup();
+        contract.jackpot = 5_000;
+        let account_id = "carol_near".to_string();
+        assert!(!contract.roulette_jackpot_hit(&account_id, 0, 100));
+        assert!(!contract.roulette_jackpot_hit(&account_id, 0, 100));
+        assert!(!contract.roulette_jackpot_hit(&account_id, 7, 100));
+        assert_eq!(0, contract.roulette_zero_streaks.get(&account_id).unwrap_or(0));
+        assert!(!contract.roulette_jackpot_hit(&account_id, 0, 100));
+        assert!(!contract.roulette_jackpot_hit(&account_id, 0, 99));
+        assert_eq!(0, contract.roulette_zero_streaks.get(&account_id).unwrap_or(0));
+        assert!(!contract.roulette_jackpot_hit(&account_id, 0, 100));
+        assert!(!contract.roulette_jackpot_hit(&account_id, 0, 100));
+        assert!(contract.roulette_jackpot_hit(&account_id, 0, 100));
+
+        let mut account = contract.users.get(&account_id).unwrap();
+        let won = contract.settle_with_jackpot(&mut account, 1000, 0, "roulette", true);
+        assert_eq!(5_010, won);
+        assert_eq!(0, contract.get_jackpot());
+        assert_eq!(10_000 - 1000 + 5_010, contract.users.get(&account_id).unwrap().deposit);
+
+        let history = contract.get_jackpot_history(0, 10);
+        assert_eq!(1, history.len());
+        assert_eq!(5_010, history[0].amount);
+        assert_eq!("roulette", history[0].game);
+        assert!(get_logs().last().unwrap().contains("\"event\":\"jackpot_win\""));
+    }
+
+    #[test]
+    fn only_the_configured_triple_hits() {
+        let contract = setup();
+        assert!(contract.sicbo_jackpot_hit(&[6, 6, 6], 100));
+        assert!(!contract.sicbo_jackpot_hit(&[5, 5, 5], 100));
+        assert!(!contract.sicbo_jackpot_hit(&[6, 6, 5], 100));
+    }
+
+    #[test]
+    fn dust_bets_cannot_hit() {
+        let contract = setup();
+        assert!(!contract.sicbo_jackpot_hit(&[6, 6, 6], 99));
+        assert!(!contract.dice_jackpot_hit(7_777, 99));
+        assert!(contract.dice_jackpot_hit(7_777, 100));
+    }
+}
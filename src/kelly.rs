@@ -0,0 +1,160 @@
+use near_sdk::near_bindgen;
+use near_sdk::Balance;
+use std::collections::HashMap;
+
+use crate::baccarat::baccarat_paytable;
+use crate::keno::{keno_weights, KENO_MAX_SPOTS};
+use crate::payout::{kelly_max_bet, Multiplier, BASIS_POINTS};
+use crate::plinko::{plinko_paytable, plinko_weights, PlinkoRisk};
+use crate::*;
+
+// Half Kelly, which gives up a quarter of the growth rate for half the bankroll swings
+pub const DEFAULT_KELLY_FRACTION_BPS: u32 = 5_000;
+
+#[near_bindgen]
+impl Contract {
+    // The largest stake each bet key accepts right now, so a UI can disable oversized chips
+    pub fn get_sicbo_max_bets(&self, bets: Vec<Bet>) -> HashMap<Bet, Balance> {
+        bets.into_iter().map(|bet| {
+            let max_bet = self.sicbo_max_bet(&bet);
+            (bet, max_bet)
+        }).collect()
+    }
+
+    pub fn get_roulette_max_bets(&self, bets: Vec<Bet>) -> HashMap<Bet, Balance> {
+        bets.into_iter().map(|bet| {
+            let max_bet = self.roulette_max_bet(&bet);
+            (bet, max_bet)
+        }).collect()
+    }
+
+    pub fn get_baccarat_max_bets(&self, bets: Vec<Bet>) -> HashMap<Bet, Balance> {
+        bets.into_iter().map(|bet| {
+            let max_bet = self.baccarat_max_bet(&bet);
+            (bet, max_bet)
+        }).collect()
+    }
+
+    pub fn get_dice_max_bet(&self, mode: DiceMode, target: u16) -> Balance {
+        self.dice_max_bet(mode, target)
+    }
+
+    pub fn get_keno_max_bet(&self, spots: u8) -> Balance {
+        assert!((1..=KENO_MAX_SPOTS).contains(&spots), "ERR_SPOTS_NOT_VALID");
+        self.keno_max_bet(spots)
+    }
+
+    pub fn get_plinko_max_bet(&self, risk: PlinkoRisk, rows: u8) -> Balance {
+        self.plinko_max_bet(risk, rows)
+    }
+
+    pub fn get_kelly_fraction(&self) -> u32 {
+        self.kelly_fraction_bps
+    }
+
+    pub fn set_kelly_fraction(&mut self, kelly_fraction_bps: u32) {
+        self.assert_owner();
+        assert!(kelly_fraction_bps as u128 <= BASIS_POINTS, "ERR_KELLY_FRACTION_NOT_VALID");
+        self.kelly_fraction_bps = kelly_fraction_bps;
+    }
+}
+
+// Limits are taken against the bankroll before the stakes of the bet are added to it, less what
+// open rounds have reserved
+impl Contract {
+    pub(crate) fn sicbo_max_bet(&self, bet: &str) -> Balance {
+        let paytable: Vec<Multiplier> = (0..SICBO_OUTCOMES)
+            .map(|outcome| Multiplier::whole(check_point_sicbo(bet, sicbo_dices(outcome))))
+            .collect();
+        kelly_max_bet(self.free_house(), self.kelly_fraction_bps, &paytable, &[1; SICBO_OUTCOMES as usize])
+    }
+
+    pub(crate) fn roulette_max_bet(&self, bet: &str) -> Balance {
+        let paytable: Vec<Multiplier> = (0..ROULETTE_OUTCOMES)
+            .map(|outcome| Multiplier::whole(check_point_roulette(bet, outcome as u8)))
+            .collect();
+        kelly_max_bet(self.free_house(), self.kelly_fraction_bps, &paytable, &[1; ROULETTE_OUTCOMES as usize])
+    }
+
+    pub(crate) fn baccarat_max_bet(&self, bet: &str) -> Balance {
+        let (paytable, weights) = baccarat_paytable(bet, self.baccarat_commission_bps);
+        kelly_max_bet(self.free_house(), self.kelly_fraction_bps, &paytable, &weights)
+    }
+
+    pub(crate) fn dice_max_bet(&self, mode: DiceMode, target: u16) -> Balance {
+        let multiplier = dice_multiplier(mode, target);
+        let winning_outcomes = dice_winning_outcomes(mode, target);
+        let weights = [winning_outcomes as u128, (DICE_OUTCOMES - winning_outcomes) as u128];
+        kelly_max_bet(self.free_house(), self.kelly_fraction_bps, &[multiplier, Multiplier::ZERO], &weights)
+    }
+
+    pub(crate) fn keno_max_bet(&self, spots: u8) -> Balance {
+        let paytable = &self.keno_paytables[spots as usize - 1];
+        kelly_max_bet(self.free_house(), self.kelly_fraction_bps, paytable, &keno_weights(spots))
+    }
+
+    pub(crate) fn plinko_max_bet(&self, risk: PlinkoRisk, rows: u8) -> Balance {
+        kelly_max_bet(self.free_house(), self.kelly_fraction_bps, &plinko_paytable(risk, rows), &plinko_weights(rows))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{bets, get_context};
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn limits_follow_the_bankroll_and_the_odds() {
+        testing_env!(get_context(vec![], false));
+        let mut contract = Contract { house: 1_000_000, ..Default::default() };
+        let limits = contract.get_roulette_max_bets(vec!["red".to_string(), "7".to_string()]);
+        assert!(limits["red"] > 20 * limits["7"]);
+        assert_eq!(contract.get_dice_max_bet(DiceMode::RollUnder, 5000), contract.get_dice_max_bet(DiceMode::RollOver, 4999));
+
+        contract.house *= 2;
+        assert_eq!(2 * limits["7"], contract.get_roulette_max_bets(vec!["7".to_string()])["7"]);
+        contract.kelly_fraction_bps /= 2;
+        assert_eq!(limits["7"], contract.get_roulette_max_bets(vec!["7".to_string()])["7"]);
+
+        // Reserved for open rounds, the bankroll cannot back new bets
+        contract.kelly_fraction_bps *= 2;
+        contract.reserve_house(1_000_000);
+        assert_eq!(limits["7"], contract.get_roulette_max_bets(vec!["7".to_string()])["7"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_BET_ABOVE_MAX")]
+    fn oversized_bets_are_rejected() {
+        let mut context = get_context(vec![], false);
+        context.attached_deposit = 100_000;
+        testing_env!(context);
+        let mut contract = Contract { house: 1_000_000, ..Default::default() };
+        contract.deposit(None);
+        let max_bet = contract.get_sicbo_max_bets(vec!["triple_1".to_string()])["triple_1"];
+        contract.play_sicbo(bets(&[("triple_1", max_bet + 1)]), None);
+    }
+
+    #[test]
+    fn keno_and_plinko_limits_shrink_with_the_top_prize() {
+        testing_env!(get_context(vec![], true));
+        let contract = Contract { house: 10u128.pow(12), ..Default::default() };
+        assert!(contract.get_keno_max_bet(1) > contract.get_keno_max_bet(10));
+        assert!(contract.get_plinko_max_bet(PlinkoRisk::Low, 8) > contract.get_plinko_max_bet(PlinkoRisk::High, 16));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_BET_ABOVE_MAX")]
+    fn table_bets_are_capped_per_key() {
+        let mut context = get_context(vec![], false);
+        context.attached_deposit = 100_000;
+        testing_env!(context);
+        let mut contract = Contract { house: 1_000_000, ..Default::default() };
+        contract.deposit(None);
+        let max_bet = contract.get_roulette_max_bets(vec!["7".to_string()])["7"];
+        contract.place_table_bet(bets(&[("7", max_bet)]));
+        contract.place_table_bet(bets(&[("7", 1)]));
+    }
+}
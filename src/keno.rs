@@ -0,0 +1,195 @@
+use near_sdk::{env, near_bindgen, AccountId, Balance};
+use near_sdk::json_types::U128;
+use serde::{Serialize, Deserialize};
+
+use crate::payout::{combinations, expected_return_bps, Multiplier, BASIS_POINTS};
+use crate::*;
+
+const KENO_NUMBERS: u8 = 80;
+const KENO_DRAWN: u8 = 20;
+pub(crate) const KENO_MAX_SPOTS: u8 = 10;
+
+// Gross return in basis points by number of hits, one row per spot count
+const DEFAULT_PAYTABLES: [&[u128]; KENO_MAX_SPOTS as usize] = [
+    &[0, 38_000],
+    &[0, 10_000, 90_000],
+    &[0, 0, 20_000, 460_000],
+    &[0, 0, 20_000, 50_000, 900_000],
+    &[0, 0, 10_000, 30_000, 150_000, 3_500_000],
+    &[0, 0, 5_000, 20_000, 60_000, 600_000, 13_000_000],
+    &[0, 0, 5_000, 10_000, 40_000, 200_000, 1_500_000, 40_000_000],
+    &[0, 0, 0, 10_000, 20_000, 100_000, 800_000, 10_000_000, 100_000_000],
+    &[0, 0, 0, 10_000, 20_000, 40_000, 250_000, 2_000_000, 25_000_000, 100_000_000],
+    &[0, 0, 0, 0, 15_000, 30_000, 160_000, 1_400_000, 10_000_000, 50_000_000, 100_000_000],
+];
+
+#[derive(Serialize, Deserialize)]
+pub struct KenoResult {
+    account_id: AccountId,
+    picks: Vec<u8>,
+    drawn: Vec<u8>,
+    hits: Vec<u8>,
+    total_winning: Balance,
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn play_keno(&mut self, picks: Vec<u8>, bet: U128) -> KenoResult {
+        assert_valid_picks(&picks);
+        let top_prize = self.keno_paytables[picks.len() - 1].iter().max().cloned().unwrap_or(Multiplier::ZERO);
+        self.assert_exposure(top_prize.apply(bet.0), bet.0);
+        assert!(bet.0 <= self.keno_max_bet(picks.len() as u8), "ERR_BET_ABOVE_MAX");
+        let account_id = env::predecessor_account_id();
+        let mut account = self.users.get(&account_id).expect("Account Id does not exist");
+
+        let drawn = self.draw_keno();
+        let hits: Vec<u8> = picks.iter().filter(|pick| drawn.contains(pick)).cloned().collect();
+        let multiplier = self.keno_paytables[picks.len() - 1][hits.len()];
+        let total_winning = multiplier.apply(bet.0);
+
+        self.settle(&mut account, "keno", bet.0, total_winning);
+
+        KenoResult {
+            account_id,
+            picks,
+            drawn,
+            hits,
+            total_winning,
+        }
+    }
+
+    pub fn get_keno_paytable(&self, spots: u8) -> Vec<Multiplier> {
+        assert!((1..=KENO_MAX_SPOTS).contains(&spots), "ERR_SPOTS_NOT_VALID");
+        self.keno_paytables[spots as usize - 1].clone()
+    }
+
+    // Rejects tables that would return the player more than they stake on average
+    pub fn set_keno_paytable(&mut self, spots: u8, paytable: Vec<Multiplier>) {
+        self.assert_owner();
+        assert!((1..=KENO_MAX_SPOTS).contains(&spots), "ERR_SPOTS_NOT_VALID");
+        assert_eq!(paytable.len(), spots as usize + 1, "ERR_PAYTABLE_NOT_VALID");
+        assert!(keno_return_bps(spots, &paytable) < BASIS_POINTS, "ERR_PAYTABLE_RETURN_TOO_HIGH");
+        self.keno_paytables[spots as usize - 1] = paytable;
+    }
+}
+
+impl Contract {
+    fn draw_keno(&mut self) -> Vec<u8> {
+        let mut numbers: Vec<u8> = (1..=KENO_NUMBERS).collect();
+        for index in 0..KENO_DRAWN as usize {
+            let remaining = KENO_NUMBERS as usize - index;
+            let swap = index + self.next_random_number() as usize % remaining;
+            numbers.swap(index, swap);
+        }
+        numbers.truncate(KENO_DRAWN as usize);
+        numbers.sort_unstable();
+        numbers
+    }
+}
+
+pub(crate) fn default_paytables() -> Vec<Vec<Multiplier>> {
+    DEFAULT_PAYTABLES
+        .iter()
+        .map(|row| row.iter().map(|bps| Multiplier::from_bps(*bps)).collect())
+        .collect()
+}
+
+fn assert_valid_picks(picks: &[u8]) {
+    assert!(!picks.is_empty() && picks.len() <= KENO_MAX_SPOTS as usize, "ERR_SPOTS_NOT_VALID");
+    for (index, pick) in picks.iter().enumerate() {
+        assert!((1..=KENO_NUMBERS).contains(pick), "ERR_PICK_NOT_VALID");
+        assert!(!picks[..index].contains(pick), "ERR_DUPLICATE_PICK");
+    }
+}
+
+// Each hit count is weighted by the number of draws producing it,
+// C(spots, hits) * C(80 - spots, 20 - hits), out of C(80, 20)
+pub(crate) fn keno_weights(spots: u8) -> Vec<u128> {
+    let spots = spots as u128;
+    let numbers = KENO_NUMBERS as u128;
+    let drawn = KENO_DRAWN as u128;
+    (0..=spots)
+        .map(|hits| combinations(spots, hits) * combinations(numbers - spots, drawn - hits))
+        .collect()
+}
+
+// The smallest edge of any spot count, with the return rounded up so the edge is never overstated
+pub(crate) fn keno_min_edge_bps(paytables: &[Vec<Multiplier>]) -> u128 {
+    paytables
+        .iter()
+        .enumerate()
+        .map(|(index, paytable)| BASIS_POINTS.saturating_sub(keno_return_bps(index as u8 + 1, paytable) + 1))
+        .min()
+        .unwrap_or(0)
+}
+
+fn keno_return_bps(spots: u8, paytable: &[Multiplier]) -> u128 {
+    let outcomes = combinations(KENO_NUMBERS as u128, KENO_DRAWN as u128);
+    expected_return_bps(paytable, &keno_weights(spots), outcomes)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::get_context;
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn default_paytables_have_exact_rtp() {
+        let expected = [9500, 9208, 9157, 9172, 9293, 9384, 9272, 9540, 9547, 9516];
+        for (index, paytable) in default_paytables().iter().enumerate() {
+            let spots = index as u8 + 1;
+            // The hit distribution covers every possible draw exactly once
+            let draws: u128 = (0..=spots as u128)
+                .map(|hits| combinations(spots as u128, hits) * combinations(80 - spots as u128, 20 - hits))
+                .sum();
+            assert_eq!(combinations(80, 20), draws);
+            assert_eq!(expected[index], keno_return_bps(spots, paytable), "{} spots", spots);
+        }
+    }
+
+    #[test]
+    fn play_keno_pays_from_the_paytable() {
+        let mut context = get_context(vec![], false);
+        context.attached_deposit = 1000;
+        testing_env!(context);
+        let mut contract = Contract { house: 10u128.pow(9), ..Default::default() };
+        contract.deposit(None);
+
+        let picks: Vec<u8> = (1..=10).collect();
+        let result = contract.play_keno(picks.clone(), U128(100));
+        assert_eq!(20, result.drawn.len());
+        assert!(result.drawn.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(result.hits.iter().all(|hit| picks.contains(hit) && result.drawn.contains(hit)));
+        assert_eq!(Multiplier::from_bps(DEFAULT_PAYTABLES[9][result.hits.len()]).apply(100), result.total_winning);
+        assert_eq!(900 + result.total_winning, contract.users.get(&"carol_near".to_string()).unwrap().deposit);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_EXPOSURE_TOO_HIGH")]
+    fn top_prize_respects_exposure_limit() {
+        // 10 hits on 10 spots pays 10,000x, so 11 can cost the house more than 10% of 10^6
+        let mut context = get_context(vec![], false);
+        context.attached_deposit = 1000;
+        testing_env!(context);
+        let mut contract = Contract { house: 10u128.pow(6), ..Default::default() };
+        contract.deposit(None);
+        contract.play_keno((1..=10).collect(), U128(11));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DUPLICATE_PICK")]
+    fn duplicate_picks_are_rejected() {
+        assert_valid_picks(&[3, 7, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PAYTABLE_RETURN_TOO_HIGH")]
+    fn paytable_must_keep_a_house_edge() {
+        testing_env!(get_context(vec![], false));
+        let mut contract = Contract { owner_id: "carol_near".to_string(), ..Default::default() };
+        contract.set_keno_paytable(1, vec![Multiplier::ZERO, Multiplier::whole(4)]);
+    }
+}
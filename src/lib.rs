@@ -1,19 +1,79 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LookupMap, UnorderedMap};
-use near_sdk::{env, near_bindgen, AccountId, Balance, Promise};
+use near_sdk::collections::{LookupMap, UnorderedMap, Vector};
+use near_sdk::{env, near_bindgen, AccountId, Balance, BlockHeight, Promise};
 use std::collections::{HashMap, HashSet};
 use near_sdk::json_types::{U128, ValidAccountId};
-use std::env::set_current_dir;
 use serde::{Serialize, Deserialize};
 
+use crate::achievements::Achievement;
+use crate::blackjack::BlackjackGame;
+use crate::bonus::BonusConfig;
+use crate::coinflip::{CoinFlipChallenge, CoinFlipConfig};
+use crate::crash::{CrashBet, CrashConfig, CrashRound};
+use crate::hilo::{HiLoConfig, HiLoSession};
+use crate::jackpot::{JackpotConfig, JackpotWin};
+use crate::limits::GamingLimits;
+use crate::mines::MinesGame;
+use crate::nft::StoredToken;
+use crate::payout::{Multiplier, BASIS_POINTS};
+use crate::pool::LiquidityPosition;
+use crate::roulette_table::{RouletteRound, RouletteTableBet};
+use crate::sicbo_table::{SicBoRound, SicBoTableBet};
+use crate::tournament::Tournament;
+use crate::vip::VipConfig;
+use crate::voucher::{Voucher, VoucherCredit, VoucherGame};
+
+mod achievements;
+mod baccarat;
+mod blackjack;
+mod bonus;
+mod coinflip;
+mod crash;
+mod events;
+mod hilo;
+mod jackpot;
+mod kelly;
+mod keno;
+mod limits;
+mod migration;
+mod mines;
+mod nft;
+mod payout;
+mod plinko;
+mod pool;
+mod referral;
+mod roulette_table;
+mod sicbo_table;
+mod tournament;
+mod transfer;
+mod vip;
+mod voucher;
+
 near_sdk::setup_alloc!();
 
 pub type Bet = String;
 
+// Dice rolls are expressed in hundredths, 0.00 to 99.99
+const DICE_OUTCOMES: u32 = 10_000;
+const DICE_RETURN: Multiplier = Multiplier::from_bps(9_850);
+const DICE_MIN_CHANCE: u32 = 200;
+const DICE_MAX_CHANCE: u32 = 9_600;
+const SICBO_OUTCOMES: u32 = 216;
+const ROULETTE_OUTCOMES: u32 = 37;
+const DEFAULT_MAX_EXPOSURE_BPS: u32 = 1_000;
+
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
 pub struct Account {
     account_id: AccountId,
     deposit: Balance,
+    referrer: Option<AccountId>,
+    referral_rewards: Balance,
+    referred_accounts: u64,
+    referral_earned: Balance,
+    wagered: Balance,
+    rakeback: Balance,
+    bonus: Balance,
+    bonus_wagering: Balance,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -21,13 +81,22 @@ pub struct SicBoResult {
     account_id: AccountId,
     dices: Vec<u8>,
     total_winning: Balance,
+    jackpot: Balance,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct DiceResult {
     account_id: AccountId,
-    dice_value: u8,
+    dice_value: u16,
     total_winning: Balance,
+    jackpot: Balance,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiceMode {
+    RollUnder,
+    RollOver,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -35,37 +104,178 @@ pub struct RouletteResult {
     account_id: AccountId,
     roulette_value: u8,
     total_winning: Balance,
+    jackpot: Balance,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BetOdds {
+    bet: Bet,
+    amount: Balance,
+    max_multiplier: Multiplier,
+    winning_outcomes: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BetPreview {
+    total_stake: Balance,
+    bets: Vec<BetOdds>,
+    outcomes: u32,
+    worst_case: Balance,
+    best_case: Balance,
+    expected_value: Balance,
 }
 
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct Contract {
+    owner_id: AccountId,
     house: Balance,
+    max_exposure_bps: u32,
     users: UnorderedMap<AccountId, Account>,
+    rng_nonce: u64,
+    blackjack_games: LookupMap<AccountId, BlackjackGame>,
+    blackjack_timeout: BlockHeight,
+    baccarat_commission_bps: u32,
+    crash_config: CrashConfig,
+    crash_round_id: u64,
+    crash_rounds: LookupMap<u64, CrashRound>,
+    crash_bets: LookupMap<String, CrashBet>,
+    crash_participants: LookupMap<String, AccountId>,
+    keno_paytables: Vec<Vec<Multiplier>>,
+    hilo_config: HiLoConfig,
+    hilo_sessions: LookupMap<AccountId, HiLoSession>,
+    mines_house_edge_bps: u32,
+    mines_games: LookupMap<AccountId, MinesGame>,
+    mines_timeout: BlockHeight,
+    coinflip_config: CoinFlipConfig,
+    coinflip_next_id: u64,
+    coinflip_challenges: UnorderedMap<u64, CoinFlipChallenge>,
+    table_blocks: BlockHeight,
+    table_round_id: u64,
+    table_rounds: LookupMap<u64, RouletteRound>,
+    table_bets: LookupMap<String, RouletteTableBet>,
+    table_participants: LookupMap<String, AccountId>,
+    sicbo_table_round_id: u64,
+    sicbo_table_rounds: LookupMap<u64, SicBoRound>,
+    sicbo_table_bets: LookupMap<String, SicBoTableBet>,
+    sicbo_table_participants: LookupMap<String, AccountId>,
+    jackpot: Balance,
+    jackpot_config: JackpotConfig,
+    jackpot_history: Vector<JackpotWin>,
+    roulette_zero_streaks: LookupMap<AccountId, u8>,
+    referral_commission_bps: u32,
+    vip_config: VipConfig,
+    bonus_config: BonusConfig,
+    voucher_next_id: u64,
+    vouchers: LookupMap<AccountId, Vec<Voucher>>,
+    voucher_credit: VoucherCredit,
+    gaming_limits: LookupMap<AccountId, GamingLimits>,
+    tournament_next_id: u64,
+    tournaments: LookupMap<u64, Tournament>,
+    tournament_participants: LookupMap<String, AccountId>,
+    tournament_scores: LookupMap<String, i128>,
+    account_tournaments: LookupMap<AccountId, Vec<u64>>,
+    nft_tokens: LookupMap<String, StoredToken>,
+    achievements: LookupMap<AccountId, Vec<Achievement>>,
+    dice_rolls: LookupMap<AccountId, u64>,
+    pool_shares: Balance,
+    pool_positions: LookupMap<AccountId, LiquidityPosition>,
+    pool_unlock_delay: u64,
+    pool_epoch: u64,
+    house_liability: Balance,
+    kelly_fraction_bps: u32,
 }
 
 impl Default for Contract {
     fn default() -> Self {
         Self {
+            owner_id: env::current_account_id(),
             house: 0,
+            max_exposure_bps: DEFAULT_MAX_EXPOSURE_BPS,
             users: UnorderedMap::new(b"r".to_vec()),
+            rng_nonce: 0,
+            blackjack_games: LookupMap::new(b"b".to_vec()),
+            blackjack_timeout: blackjack::DEFAULT_TIMEOUT,
+            baccarat_commission_bps: baccarat::DEFAULT_COMMISSION_BPS,
+            crash_config: CrashConfig::default(),
+            crash_round_id: 0,
+            crash_rounds: LookupMap::new(b"cr".to_vec()),
+            crash_bets: LookupMap::new(b"cb".to_vec()),
+            crash_participants: LookupMap::new(b"cp".to_vec()),
+            keno_paytables: keno::default_paytables(),
+            hilo_config: HiLoConfig::default(),
+            hilo_sessions: LookupMap::new(b"h".to_vec()),
+            mines_house_edge_bps: mines::DEFAULT_HOUSE_EDGE_BPS,
+            mines_games: LookupMap::new(b"m".to_vec()),
+            mines_timeout: mines::DEFAULT_TIMEOUT,
+            coinflip_config: CoinFlipConfig::default(),
+            coinflip_next_id: 0,
+            coinflip_challenges: UnorderedMap::new(b"f".to_vec()),
+            table_blocks: roulette_table::DEFAULT_TABLE_BLOCKS,
+            table_round_id: 0,
+            table_rounds: LookupMap::new(b"wr".to_vec()),
+            table_bets: LookupMap::new(b"wb".to_vec()),
+            table_participants: LookupMap::new(b"wp".to_vec()),
+            sicbo_table_round_id: 0,
+            sicbo_table_rounds: LookupMap::new(b"sr".to_vec()),
+            sicbo_table_bets: LookupMap::new(b"sb".to_vec()),
+            sicbo_table_participants: LookupMap::new(b"sp".to_vec()),
+            jackpot: 0,
+            jackpot_config: JackpotConfig::default(),
+            jackpot_history: Vector::new(b"j".to_vec()),
+            roulette_zero_streaks: LookupMap::new(b"z".to_vec()),
+            referral_commission_bps: referral::DEFAULT_REFERRAL_COMMISSION_BPS,
+            vip_config: VipConfig::default(),
+            bonus_config: BonusConfig::default(),
+            voucher_next_id: 0,
+            vouchers: LookupMap::new(b"v".to_vec()),
+            voucher_credit: VoucherCredit::Bonus,
+            gaming_limits: LookupMap::new(b"l".to_vec()),
+            tournament_next_id: 0,
+            tournaments: LookupMap::new(b"t".to_vec()),
+            tournament_participants: LookupMap::new(b"tp".to_vec()),
+            tournament_scores: LookupMap::new(b"u".to_vec()),
+            account_tournaments: LookupMap::new(b"e".to_vec()),
+            nft_tokens: LookupMap::new(b"n".to_vec()),
+            achievements: LookupMap::new(b"a".to_vec()),
+            dice_rolls: LookupMap::new(b"k".to_vec()),
+            pool_shares: 0,
+            pool_positions: LookupMap::new(b"p".to_vec()),
+            pool_unlock_delay: pool::DEFAULT_UNLOCK_DELAY,
+            pool_epoch: 0,
+            house_liability: 0,
+            kelly_fraction_bps: kelly::DEFAULT_KELLY_FRACTION_BPS,
         }
     }
 }
 
 #[near_bindgen]
 impl Contract {
-    pub fn deposit(&mut self) -> Balance {
+    // Only the first deposit, which creates the account, can register a referrer
+    pub fn deposit(&mut self, referrer: Option<ValidAccountId>) -> Balance {
         let account_id = env::predecessor_account_id();
 
-        let user = self.users.get(&account_id.clone().to_string());
+        let user = self.users.get(&account_id);
         let deposit: Balance = env::attached_deposit();
+        self.check_deposit_limits(&account_id, deposit);
         if let Some(mut user) = user {
             user.deposit += deposit;
             self.users.insert(&account_id, &user);
             user.deposit
         } else {
-            let user = Account { account_id: account_id.clone(), deposit: deposit };
+            let referrer = referrer.map(|referrer| self.register_referrer(&account_id, referrer.into()));
+            let user = Account {
+                account_id: account_id.clone(),
+                deposit,
+                referrer,
+                referral_rewards: 0,
+                referred_accounts: 0,
+                referral_earned: 0,
+                wagered: 0,
+                rakeback: 0,
+                bonus: 0,
+                bonus_wagering: 0,
+            };
             self.users.insert(&account_id, &user);
             user.deposit
         }
@@ -74,8 +284,10 @@ impl Contract {
     pub fn withdraw(&mut self, amount: Balance) -> Balance {
         let account_id = env::predecessor_account_id();
 
-        let user = self.users.get(&account_id.clone().to_string());
+        let user = self.users.get(&account_id);
         if let Some(mut user) = user {
+            // Only cash can leave the contract, bonus funds stay locked until they are wagered
+            assert!(amount <= user.deposit, "ERR_WITHDRAW_EXCEEDS_CASH");
             user.deposit -= amount;
             Promise::new(account_id.clone()).transfer(amount);
             self.users.insert(&account_id, &user);
@@ -89,158 +301,390 @@ impl Contract {
         self.users.get(&account_id.to_string()).expect("Account Id does not exist")
     }
 
-    // Total slots
-    pub fn play_sicbo(&mut self, bets: HashMap<Bet, U128>) -> SicBoResult {
+    // Total slots. A voucher pays for the bets instead of the deposit.
+    pub fn play_sicbo(&mut self, bets: HashMap<Bet, U128>, voucher_id: Option<u64>) -> SicBoResult {
         let account_id = env::predecessor_account_id();
-        let mut account = self.users.get(&account_id.to_string()).expect("Account Id does not exist");
+        let mut account = self.users.get(&account_id).expect("Account Id does not exist");
+        if let Some(voucher_id) = voucher_id {
+            self.redeem_voucher(&account_id, voucher_id, VoucherGame::Sicbo, &bets);
+        }
+        self.assert_sicbo_limits(&bets);
 
         let mut total: u128 = 0;
-        let dices: Vec<u8> = roll_dices();
+        let dices: Vec<u8> = self.roll_dices();
         let mut total_winning: u128 = 0;
+        self.check_sicbo_achievements(&account_id, bets.keys(), &dices);
 
         for (bet, balance) in bets {
-            let point = check_point_sicbo(&bet, dices.clone());
-            total += balance.0;
-            total_winning += point as u128 * balance.0;
+            let multiplier = Multiplier::whole(check_point_sicbo(&bet, dices.clone()));
+            total = add_balance(total, balance.0);
+            total_winning = add_balance(total_winning, multiplier.apply(balance.0));
         }
 
-        assert!(
-            total <= account.deposit,
-            "Account deposit does not sufficient for the bet"
-        );
-
-        account.deposit = account.deposit - total + total_winning;
-        self.house = self.house + total - total_winning;
-        self.users.insert(&account_id, &account);
+        let jackpot = if voucher_id.is_some() {
+            self.pay_voucher(&mut account, total_winning);
+            0
+        } else {
+            let hit = self.sicbo_jackpot_hit(&dices, total);
+            self.settle_with_jackpot(&mut account, total, total_winning, "sicbo", hit)
+        };
 
         SicBoResult {
-            account_id: account_id,
-            dices: dices,
-            total_winning: total_winning,
+            account_id,
+            dices,
+            total_winning,
+            jackpot,
         }
     }
 
-    pub fn play_roulette(&mut self, bets: HashMap<Bet, U128>) -> RouletteResult {
+    pub fn play_roulette(&mut self, bets: HashMap<Bet, U128>, voucher_id: Option<u64>) -> RouletteResult {
         let account_id = env::predecessor_account_id();
-        let mut account = self.users.get(&account_id.to_string()).expect("Account Id does not exist");
+        let mut account = self.users.get(&account_id).expect("Account Id does not exist");
+        if let Some(voucher_id) = voucher_id {
+            self.redeem_voucher(&account_id, voucher_id, VoucherGame::Roulette, &bets);
+        }
+        self.assert_roulette_limits(&bets);
 
         let mut total: u128 = 0;
-        let roulette_value: u8 = roulette_spin();
+        let roulette_value: u8 = self.roulette_spin();
         let mut total_winning: u128 = 0;
+        self.check_roulette_achievements(&account_id, bets.keys(), roulette_value);
         for (bet, balance) in bets {
-            let point = check_point_roulette(&bet, roulette_value);
-            total += balance.0;
-            total_winning += point as u128 * balance.0;
+            let multiplier = Multiplier::whole(check_point_roulette(&bet, roulette_value));
+            total = add_balance(total, balance.0);
+            total_winning = add_balance(total_winning, multiplier.apply(balance.0));
         }
 
-        assert!(
-            total <= account.deposit,
-            "Account deposit does not sufficient for the bet"
-        );
-
-        account.deposit = account.deposit - total + total_winning;
-        self.house = self.house + total - total_winning;
-        self.users.insert(&account_id, &account);
+        let jackpot = if voucher_id.is_some() {
+            self.pay_voucher(&mut account, total_winning);
+            0
+        } else {
+            let hit = self.roulette_jackpot_hit(&account_id, roulette_value, total);
+            self.settle_with_jackpot(&mut account, total, total_winning, "roulette", hit)
+        };
 
         RouletteResult {
             account_id,
             roulette_value,
             total_winning,
+            jackpot,
         }
     }
 
-    pub fn play_dice(&mut self, roll_under: u8, bet: U128) -> DiceResult {
-        assert!(roll_under > 1 && roll_under < 97, "roll_under guess is not at the valid range");
+    // Target is in hundredths, e.g. 4950 wins under 49.50 or over 49.50
+    pub fn play_dice(&mut self, mode: DiceMode, target: u16, bet: U128, voucher_id: Option<u64>) -> DiceResult {
+        let multiplier = dice_multiplier(mode, target);
+        self.assert_dice_limits(mode, target, bet.0);
         let account_id = env::predecessor_account_id();
-        let mut account = self.users.get(&account_id.to_string()).expect("Account Id does not exist");
-
-        assert!(bet.0 <= account.deposit, "Account deposit does not sufficient for the bet");
+        let mut account = self.users.get(&account_id).expect("Account Id does not exist");
+        if let Some(voucher_id) = voucher_id {
+            let mut bets = HashMap::new();
+            bets.insert(format!("{}_{}", mode.as_str(), target), bet);
+            self.redeem_voucher(&account_id, voucher_id, VoucherGame::Dice, &bets);
+        }
 
-        let dice_value: u8 = dice_100_roll();
-        let total_winning: u128 = if dice_value < roll_under {
-            let times: f64 = 98.50/(roll_under as f64 -1.0);
-            ((times * 1000.0) as u128 * bet.0) / 1000u128
+        let dice_value: u16 = self.dice_10000_roll();
+        self.record_dice_roll(&account_id);
+        let total_winning: u128 = if dice_wins(mode, target, dice_value) {
+            multiplier.apply(bet.0)
         } else {
             0u128
         };
-        account.deposit = account.deposit - bet.0 + total_winning;
-        self.house = self.house + bet.0 - total_winning;
-        self.users.insert(&account_id, &account);
+
+        let jackpot = if voucher_id.is_some() {
+            self.pay_voucher(&mut account, total_winning);
+            0
+        } else {
+            let hit = self.dice_jackpot_hit(dice_value, bet.0);
+            self.settle_with_jackpot(&mut account, bet.0, total_winning, "dice", hit)
+        };
 
         DiceResult {
             account_id,
             dice_value,
-            total_winning
+            total_winning,
+            jackpot,
+        }
+
+    }
+
+    pub fn get_max_exposure(&self) -> u32 {
+        self.max_exposure_bps
+    }
+
+    pub fn set_max_exposure(&mut self, max_exposure_bps: u32) {
+        self.assert_owner();
+        assert!(max_exposure_bps as u128 <= BASIS_POINTS, "ERR_EXPOSURE_NOT_VALID");
+        self.max_exposure_bps = max_exposure_bps;
+    }
+
+    // Previews run the same limits as play_*, so they only accept bets a play would accept
+    pub fn preview_sicbo(&self, bets: HashMap<Bet, U128>) -> BetPreview {
+        self.assert_sicbo_limits(&bets);
+        preview_bets(bets, SICBO_OUTCOMES, sicbo_multiplier)
+    }
+
+    pub fn preview_roulette(&self, bets: HashMap<Bet, U128>) -> BetPreview {
+        self.assert_roulette_limits(&bets);
+        preview_bets(bets, ROULETTE_OUTCOMES, roulette_multiplier)
+    }
+
+    pub fn preview_dice(&self, mode: DiceMode, target: u16, bet: U128) -> BetPreview {
+        self.assert_dice_limits(mode, target, bet.0);
+        let multiplier = dice_multiplier(mode, target);
+        let mut bets = HashMap::new();
+        bets.insert(format!("{}_{}", mode.as_str(), target), bet);
+        preview_bets(bets, DICE_OUTCOMES, |_, outcome| {
+            if dice_wins(mode, target, outcome as u16) { multiplier } else { Multiplier::ZERO }
+        })
+    }
+}
+
+impl Contract {
+    fn assert_owner(&self) {
+        assert_eq!(env::predecessor_account_id(), self.owner_id, "ERR_NOT_OWNER");
+    }
+
+    // Rejects bets whose best case would cost the house more than its exposure share of the bankroll
+    fn assert_exposure(&self, best_case: Balance, total: Balance) {
+        let max_loss = self.free_house() * self.max_exposure_bps as u128 / BASIS_POINTS;
+        assert!(best_case.saturating_sub(total) <= max_loss, "ERR_EXPOSURE_TOO_HIGH");
+    }
+
+    fn assert_sicbo_limits(&self, bets: &HashMap<Bet, U128>) {
+        self.assert_bet_limits(bets, SICBO_OUTCOMES, sicbo_multiplier, Self::sicbo_max_bet);
+    }
+
+    fn assert_roulette_limits(&self, bets: &HashMap<Bet, U128>) {
+        self.assert_bet_limits(bets, ROULETTE_OUTCOMES, roulette_multiplier, Self::roulette_max_bet);
+    }
+
+    fn assert_dice_limits(&self, mode: DiceMode, target: u16, bet: Balance) {
+        assert!(bet <= self.dice_max_bet(mode, target), "ERR_BET_ABOVE_MAX");
+        self.assert_exposure(dice_multiplier(mode, target).apply(bet), bet);
+    }
+
+    // Each bet key must stay under its Kelly limit, and the best case of all of them together
+    // under the exposure limit
+    fn assert_bet_limits<F, M>(&self, bets: &HashMap<Bet, U128>, outcomes: u32, multiplier_of: F, max_bet_of: M)
+    where
+        F: Fn(&str, u32) -> Multiplier,
+        M: Fn(&Self, &str) -> Balance,
+    {
+        let best_case = (0..outcomes)
+            .map(|outcome| bets.iter().fold(0, |total, (bet, amount)| add_balance(total, multiplier_of(bet, outcome).apply(amount.0))))
+            .max()
+            .unwrap_or(0);
+        for (bet, amount) in bets.iter() {
+            assert!(amount.0 <= max_bet_of(self, bet), "ERR_BET_ABOVE_MAX");
+        }
+        let total = bets.values().fold(0, |total, amount| add_balance(total, amount.0));
+        self.assert_exposure(best_case, total);
+    }
+
+    fn settle(&mut self, account: &mut Account, game: &str, total: Balance, total_winning: Balance) {
+        let bonus_stake = self.collect_stake(account, total);
+        self.pay_winning(account, game, total, bonus_stake, total_winning, env::block_timestamp(), false);
+        self.users.insert(&account.account_id, account);
+    }
+
+    // Multi-step games hold the stake in the house until the hand is settled, along with the
+    // part of it that came from the bonus balance, which is returned here
+    fn collect_stake(&mut self, account: &mut Account, total: Balance) -> Balance {
+        self.check_stake_limits(&account.account_id, total);
+        let bonus_stake = self.draw_stake(account, total);
+        self.house = add_balance(self.house, total);
+        bonus_stake
+    }
+
+    // Every settled bet passes through here once, with the game, stake and time it was placed with.
+    // A void round was closed without a guess or reveal, e.g. when a hi-lo session expires
+    // untouched, so it earns no rakeback, volume, commission, tournament score or progress on a
+    // bonus wagering requirement
+    #[allow(clippy::too_many_arguments)]
+    fn pay_winning(
+        &mut self,
+        account: &mut Account,
+        game: &str,
+        stake: Balance,
+        bonus_stake: Balance,
+        total_winning: Balance,
+        placed_at: u64,
+        void: bool,
+    ) {
+        self.house = self.house
+            .checked_sub(total_winning)
+            .expect("ERR_HOUSE_BALANCE_NOT_SUFFICIENT");
+        self.credit_winning(account, stake, bonus_stake, total_winning);
+        if !void {
+            self.wager_bonus(account, stake);
+            self.accrue_referral(account, game, stake);
+            self.accrue_rakeback(account, game, stake);
+            self.record_tournament_play(&account.account_id, game, stake, total_winning, placed_at);
+        }
+        self.record_loss(&account.account_id, stake, total_winning);
+    }
+
+    // Each draw hashes the block seed with a fresh nonce, for games that need many numbers per call
+    fn next_random_number(&mut self) -> u32 {
+        let number = get_random_number_at(self.rng_nonce);
+        self.rng_nonce += 1;
+        number
+    }
+
+    fn roll_dices(&mut self) -> Vec<u8> {
+        (0..3).map(|_| (self.next_random_number() % 6 + 1) as u8).collect()
+    }
+
+    fn roulette_spin(&mut self) -> u8 {
+        (self.next_random_number() % 37) as u8
+    }
+
+    fn dice_10000_roll(&mut self) -> u16 {
+        (self.next_random_number() % DICE_OUTCOMES) as u16
+    }
+}
+
+// Evaluates every equally likely outcome of a game against the bets without touching any state
+fn preview_bets<F: Fn(&str, u32) -> Multiplier>(bets: HashMap<Bet, U128>, outcomes: u32, multiplier_of: F) -> BetPreview {
+    let mut odds: Vec<BetOdds> = bets
+        .into_iter()
+        .map(|(bet, amount)| BetOdds { bet, amount: amount.0, max_multiplier: Multiplier::ZERO, winning_outcomes: 0 })
+        .collect();
+    odds.sort_by(|a, b| a.bet.cmp(&b.bet));
+
+    let mut worst_case: u128 = u128::MAX;
+    let mut best_case: u128 = 0;
+    let mut sum_winning: u128 = 0;
+    for outcome in 0..outcomes {
+        let mut total_winning: u128 = 0;
+        for bet_odds in odds.iter_mut() {
+            let multiplier = multiplier_of(&bet_odds.bet, outcome);
+            if !multiplier.is_zero() {
+                bet_odds.winning_outcomes += 1;
+                bet_odds.max_multiplier = bet_odds.max_multiplier.max(multiplier);
+            }
+            total_winning = add_balance(total_winning, multiplier.apply(bet_odds.amount));
+        }
+        worst_case = worst_case.min(total_winning);
+        best_case = best_case.max(total_winning);
+        sum_winning = add_balance(sum_winning, total_winning);
+    }
+
+    BetPreview {
+        total_stake: odds.iter().fold(0, |total, bet_odds| add_balance(total, bet_odds.amount)),
+        bets: odds,
+        outcomes,
+        worst_case,
+        best_case,
+        expected_value: sum_winning / outcomes as u128,
+    }
+}
+
+fn add_balance(a: Balance, b: Balance) -> Balance {
+    a.checked_add(b).expect("ERR_BALANCE_OVERFLOW")
+}
+
+impl DiceMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            DiceMode::RollUnder => "roll_under",
+            DiceMode::RollOver => "roll_over",
         }
+    }
+}
+
+// Reads back a dice bet key as written by `preview_dice`, e.g. `roll_over_4950`
+fn parse_dice_bet(bet: &str) -> (DiceMode, u16) {
+    let (mode, target) = if let Some(target) = bet.strip_prefix("roll_under_") {
+        (DiceMode::RollUnder, target)
+    } else if let Some(target) = bet.strip_prefix("roll_over_") {
+        (DiceMode::RollOver, target)
+    } else {
+        env::panic(b"ERR_UNKNOWN_BET")
+    };
+    (mode, target.parse().expect("ERR_DICE_TARGET_NOT_VALID"))
+}
 
+fn dice_wins(mode: DiceMode, target: u16, dice_value: u16) -> bool {
+    match mode {
+        DiceMode::RollUnder => dice_value < target,
+        DiceMode::RollOver => dice_value > target,
     }
 }
 
+// Pays the dice return over the exact win probability, rounded down in favor of the house
+fn dice_winning_outcomes(mode: DiceMode, target: u16) -> u32 {
+    assert!((target as u32) < DICE_OUTCOMES, "ERR_DICE_TARGET_NOT_VALID");
+    let winning_outcomes: u32 = match mode {
+        DiceMode::RollUnder => target as u32,
+        DiceMode::RollOver => DICE_OUTCOMES - 1 - target as u32,
+    };
+    assert!(
+        (DICE_MIN_CHANCE..=DICE_MAX_CHANCE).contains(&winning_outcomes),
+        "ERR_DICE_CHANCE_NOT_VALID"
+    );
+    winning_outcomes
+}
+
+fn dice_multiplier(mode: DiceMode, target: u16) -> Multiplier {
+    DICE_RETURN.over_probability(dice_winning_outcomes(mode, target) as u128, DICE_OUTCOMES as u128)
+}
+
+fn sicbo_multiplier(bet: &str, outcome: u32) -> Multiplier {
+    Multiplier::whole(check_point_sicbo(bet, sicbo_dices(outcome)))
+}
+
+fn roulette_multiplier(bet: &str, outcome: u32) -> Multiplier {
+    Multiplier::whole(check_point_roulette(bet, outcome as u8))
+}
+
+// Maps an index in 0..SICBO_OUTCOMES to one of the equally likely rolls of three dice
+fn sicbo_dices(outcome: u32) -> Vec<u8> {
+    vec![(outcome / 36 + 1) as u8, (outcome / 6 % 6 + 1) as u8, (outcome % 6 + 1) as u8]
+}
+
 fn check_point_sicbo(bet: &str, dices: Vec<u8>) -> u32 {
     match bet {
         "small" => {
             let sum_all: u8 = dices.iter().sum();
-            if sum_all >= 4 && sum_all <= 10 {
+            if (4..=10).contains(&sum_all) {
                 return 1;
             }
         }
         "big" => {
             let sum_all: u8 = dices.iter().sum();
-            if sum_all >= 11 && sum_all <= 17 {
+            if (11..=17).contains(&sum_all) {
                 return 1;
             }
         }
         "double_1" => {
-            if dices[0] == dices[1] && dices[0] == 1 {
-                return 10;
-            } else if dices[0] == dices[1] && dices[0] == 1 {
-                return 10;
-            } else if dices[1] == dices[2] && dices[1] == 1 {
+            if dices.iter().filter(|&&dice| dice == 1).count() >= 2 {
                 return 10;
             }
         }
         "double_2" => {
-            if dices[0] == dices[1] && dices[0] == 2 {
-                return 10;
-            } else if dices[0] == dices[1] && dices[0] == 2 {
-                return 10;
-            } else if dices[1] == dices[2] && dices[1] == 2 {
+            if dices.iter().filter(|&&dice| dice == 2).count() >= 2 {
                 return 10;
             }
         }
         "double_3" => {
-            if dices[0] == dices[1] && dices[0] == 3 {
-                return 10;
-            } else if dices[0] == dices[1] && dices[0] == 3 {
-                return 10;
-            } else if dices[1] == dices[2] && dices[1] == 3 {
+            if dices.iter().filter(|&&dice| dice == 3).count() >= 2 {
                 return 10;
             }
         }
         "double_4" => {
-            if dices[0] == dices[1] && dices[0] == 4 {
-                return 10;
-            } else if dices[0] == dices[1] && dices[0] == 4 {
-                return 10;
-            } else if dices[1] == dices[2] && dices[1] == 4 {
+            if dices.iter().filter(|&&dice| dice == 4).count() >= 2 {
                 return 10;
             }
         }
         "double_5" => {
-            if dices[0] == dices[1] && dices[0] == 5 {
-                return 10;
-            } else if dices[0] == dices[1] && dices[0] == 5 {
-                return 10;
-            } else if dices[1] == dices[2] && dices[1] == 5 {
+            if dices.iter().filter(|&&dice| dice == 5).count() >= 2 {
                 return 10;
             }
         }
         "double_6" => {
-            if dices[0] == dices[1] && dices[0] == 6 {
-                return 10;
-            } else if dices[0] == dices[1] && dices[0] == 6 {
-                return 10;
-            } else if dices[1] == dices[2] && dices[1] == 6 {
+            if dices.iter().filter(|&&dice| dice == 6).count() >= 2 {
                 return 10;
             }
         }
@@ -613,18 +1057,21 @@ fn check_point_sicbo(bet: &str, dices: Vec<u8>) -> u32 {
             return total;
         }
         _ => {
-            return 0;
+            env::panic(b"ERR_UNKNOWN_BET");
         }
     }
-    return 0;
+    0
 }
 
 fn check_point_roulette(bet: &str, roulette_value: u8) -> u32 {
     let roulette_value_str = roulette_value.to_string();
-    if roulette_value_str == bet {
-        return 35;
+    if let Ok(number) = bet.parse::<u8>() {
+        assert!(number <= 36, "ERR_STRAIGHT_NOT_VALID");
+        if number == roulette_value {
+            return 35;
+        }
     } else if bet.contains('|') { // split
-        let mut split = bet.split('|').collect::<Vec<&str>>();
+        let split = bet.split('|').collect::<Vec<&str>>();
         let mut diff: i32 = 0;
         let mut count = 0;
         let mut win = 0;
@@ -638,7 +1085,7 @@ fn check_point_roulette(bet: &str, roulette_value: u8) -> u32 {
             }
 
             numbers.insert(num.parse::<i32>().unwrap());
-            if num.to_string() == roulette_value_str {
+            if *num == roulette_value_str {
                 win = 1;
             }
         }
@@ -766,11 +1213,11 @@ fn check_point_roulette(bet: &str, roulette_value: u8) -> u32 {
             return 2;
         }
     } else if bet == "2nd_12" {
-        if roulette_value <= 24 && roulette_value > 12 {
+        if (13..=24).contains(&roulette_value) {
             return 2;
         }
     } else if bet == "3rd_12" {
-        if roulette_value <= 36 && roulette_value > 24 {
+        if (25..=36).contains(&roulette_value) {
             return 2;
         }
     } else if bet == "low" {
@@ -778,15 +1225,12 @@ fn check_point_roulette(bet: &str, roulette_value: u8) -> u32 {
             return 1;
         }
     } else if bet == "high" {
-        if roulette_value > 18 && roulette_value <= 36 {
-            return 1;
-        }
-    } else if bet == "even" {
-        if roulette_value % 2 == 0 {
+        if (19..=36).contains(&roulette_value) {
             return 1;
         }
-    } else if bet == "odd"{
-        if roulette_value % 2 == 1 {
+    } else if bet == "even" || bet == "odd" {
+        let parity = if bet == "even" { 0 } else { 1 };
+        if roulette_value % 2 == parity {
             return 1;
         }
     } else if bet == "red" {
@@ -797,38 +1241,24 @@ fn check_point_roulette(bet: &str, roulette_value: u8) -> u32 {
         if vec![2,4,6,8,10,11,13,15,17,20,22,24,26,28,29,31,33,35].contains(&(roulette_value as i32)) {
             return 1;
         }
+    } else {
+        env::panic(b"ERR_UNKNOWN_BET");
     }
     0
 }
 
-fn roll_dices() -> Vec<u8> {
-    let mut dices: Vec<u8> = Vec::new();
-    for _ in 0..3 {
-        let seed_num = get_random_number(0);
-        dices.push((seed_num % 6 + 1) as u8);
-    }
-    return dices;
-}
-
-fn roulette_spin() -> u8 {
-    let seed_num = get_random_number(0);
-    return (seed_num % 37) as u8;
-}
-
-fn dice_100_roll() -> u8 {
-    let seed_num = get_random_number(0);
-    return (seed_num % 100) as u8;
-}
-
-fn get_random_number(shift_amount: u32) -> u32 {
+// sha256 of the block seed and the nonce, so every draw in a call is independent. The first
+// four bytes of the raw seed used before this gave the same number to every draw in a block.
+fn get_random_number_at(nonce: u64) -> u32 {
     let mut seed = env::random_seed();
-    let seed_len = seed.len();
+    seed.extend_from_slice(&nonce.to_le_bytes());
+    let hash = env::sha256(&seed);
     let mut arr: [u8; 4] = Default::default();
-    seed.rotate_left(shift_amount as usize % seed_len);
-    arr.copy_from_slice(&seed[..4]);
+    arr.copy_from_slice(&hash[..4]);
     u32::from_le_bytes(arr)
 }
 
+
 #[cfg(not(target_arch = "wasm32"))]
 #[cfg(test)]
 mod tests {
@@ -836,7 +1266,7 @@ mod tests {
     use near_sdk::MockedBlockchain;
     use near_sdk::{testing_env, VMContext};
 
-    fn get_context(input: Vec<u8>, is_view: bool) -> VMContext {
+    pub(crate) fn get_context(input: Vec<u8>, is_view: bool) -> VMContext {
         VMContext {
             current_account_id: "alice_near".to_string(),
             signer_account_id: "bob_near".to_string(),
@@ -850,30 +1280,158 @@ mod tests {
             storage_usage: 0,
             attached_deposit: 0,
             prepaid_gas: 10u64.pow(18),
-            random_seed: vec![0, 1, 2],
+            random_seed: vec![0; 32],
             is_view,
             output_data_receivers: vec![],
             epoch_height: 0,
         }
     }
 
+    // Moves a running test to another caller, block, time and deposit, keeping the storage written
+    // so far. Withdrawals transfer out of the contract balance, so it is always funded.
+    pub(crate) fn set_context(predecessor: &str, block_index: BlockHeight, block_timestamp: u64, deposit: Balance) {
+        let mut context = get_context(vec![], false);
+        context.predecessor_account_id = predecessor.to_string();
+        context.block_index = block_index;
+        context.block_timestamp = block_timestamp;
+        context.attached_deposit = deposit;
+        context.account_balance = 10u128.pow(33);
+        context.storage_usage = env::storage_usage();
+        testing_env!(context);
+    }
+
+    pub(crate) fn bets(items: &[(&str, u128)]) -> HashMap<Bet, U128> {
+        items.iter().map(|(bet, amount)| (bet.to_string(), U128(*amount))).collect()
+    }
+
+    #[test]
+    fn deposit_and_play_sicbo() {
+        let mut context = get_context(vec![], false);
+        context.attached_deposit = 1000;
+        testing_env!(context);
+        let mut contract = Contract { house: 10_000, ..Default::default() };
+        assert_eq!(1000, contract.deposit(None));
+
+        // A zero seed rolls 5-2-2, and 1% of the stake goes to the jackpot
+        let result = contract.play_sicbo(bets(&[("small", 100), ("big", 100)]), None);
+        assert_eq!(vec![5, 2, 2], result.dices);
+        assert_eq!(100, result.total_winning);
+        assert_eq!(900, contract.users.get(&"carol_near".to_string()).unwrap().deposit);
+        assert_eq!(10_098, contract.house);
+        assert_eq!(2, contract.jackpot);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_UNKNOWN_BET")]
+    fn play_roulette_rejects_unknown_bet() {
+        let mut context = get_context(vec![], false);
+        context.attached_deposit = 1000;
+        testing_env!(context);
+        let mut contract = Contract::default();
+        contract.deposit(None);
+        contract.play_roulette(bets(&[("green", 100)]), None);
+    }
+
+    #[test]
+    fn preview_sicbo_enumerates_all_rolls() {
+        testing_env!(get_context(vec![], true));
+        let contract = Contract { house: 10u128.pow(9), ..Default::default() };
+        let preview = contract.preview_sicbo(bets(&[("big", 100), ("triple_6", 10)]));
+
+        assert_eq!(110, preview.total_stake);
+        assert_eq!(216, preview.outcomes);
+        assert_eq!(0, preview.worst_case);
+        assert_eq!(1800, preview.best_case);
+        // 107 rolls sum to 11-17 and 6-6-6 sums to 18, so the two bets never win together
+        assert_eq!((107 * 100 + 1800) / 216, preview.expected_value);
+        assert_eq!("big", preview.bets[0].bet);
+        assert_eq!(107, preview.bets[0].winning_outcomes);
+        assert_eq!(1, preview.bets[1].winning_outcomes);
+        assert_eq!(Multiplier::whole(180), preview.bets[1].max_multiplier);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_BET_ABOVE_MAX")]
+    fn preview_rejects_what_play_rejects() {
+        testing_env!(get_context(vec![], true));
+        let contract = Contract { house: 1_000_000, ..Default::default() };
+        let max_bet = contract.get_roulette_max_bets(vec!["7".to_string()])["7"];
+        contract.preview_roulette(bets(&[("7", max_bet + 1)]));
+    }
+
     #[test]
-    fn set_get_message() {
-        let context = get_context(vec![], false);
+    fn preview_dice_uses_exact_win_probability() {
+        testing_env!(get_context(vec![], true));
+        let contract = Contract { house: 10u128.pow(9), ..Default::default() };
+        let preview = contract.preview_dice(DiceMode::RollUnder, 5000, U128(1000));
+
+        // Rolls 0.00 to 49.99 win, paying 98.50% / 50%
+        assert_eq!(5000, preview.bets[0].winning_outcomes);
+        assert_eq!(Multiplier::from_bps(19_700), preview.bets[0].max_multiplier);
+        assert_eq!(1970, preview.best_case);
+        assert_eq!(0, preview.worst_case);
+        assert_eq!(985, preview.expected_value);
+
+        let preview = contract.preview_dice(DiceMode::RollOver, 7525, U128(1000));
+        assert_eq!("roll_over_7525", preview.bets[0].bet);
+        assert_eq!(2474, preview.bets[0].winning_outcomes);
+        assert_eq!(Multiplier::from_bps(39_814), preview.bets[0].max_multiplier);
+    }
+
+    #[test]
+    fn play_dice_roll_over() {
+        let mut context = get_context(vec![], false);
+        context.attached_deposit = 1000;
         testing_env!(context);
-        let mut contract = StatusMessage::default();
-        contract.set_status("hello".to_string());
-        assert_eq!(
-            "hello".to_string(),
-            contract.get_status("bob_near".to_string()).unwrap()
-        );
+        let mut contract = Contract { house: 100_000, ..Default::default() };
+        contract.deposit(None);
+
+        // A zero seed rolls 30.36 and then 74.17
+        let result = contract.play_dice(DiceMode::RollUnder, 5000, U128(100), None);
+        assert_eq!(3036, result.dice_value);
+        assert_eq!(197, result.total_winning);
+        let result = contract.play_dice(DiceMode::RollOver, 8000, U128(100), None);
+        assert_eq!(7417, result.dice_value);
+        assert_eq!(0, result.total_winning);
+        assert_eq!(1000 - 200 + 197, contract.users.get(&"carol_near".to_string()).unwrap().deposit);
     }
 
     #[test]
-    fn get_nonexistent_message() {
-        let context = get_context(vec![], true);
+    fn payouts_are_exact_in_yocto() {
+        let mut context = get_context(vec![], false);
+        context.attached_deposit = 10u128.pow(25);
         testing_env!(context);
-        let contract = StatusMessage::default();
-        assert_eq!(None, contract.get_status("francis.near".to_string()));
+        let mut contract = Contract { house: 10u128.pow(28), ..Default::default() };
+        contract.deposit(None);
+
+        // Under a zero seed, nonce 6 spins 0 and nonce 39 rolls 1.04
+        contract.rng_nonce = 6;
+        let result = contract.play_roulette(bets(&[("0", 10u128.pow(24) + 1)]), None);
+        assert_eq!(35 * 10u128.pow(24) + 35, result.total_winning);
+
+        contract.rng_nonce = 39;
+        let result = contract.play_dice(DiceMode::RollUnder, 250, U128(1_234_567), None);
+        assert_eq!(48_641_939, result.total_winning);
+
+        // 98.50% / 95.99% is 1.026148..., truncated to 1.0261 before it is applied
+        contract.rng_nonce = 0;
+        let result = contract.play_dice(DiceMode::RollUnder, 9_599, U128(10u128.pow(24)), None);
+        assert_eq!(1_026_100_000_000_000_000_000_000, result.total_winning);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DICE_CHANCE_NOT_VALID")]
+    fn play_dice_rejects_out_of_range_chance() {
+        testing_env!(get_context(vec![], true));
+        let contract = Contract::default();
+        contract.preview_dice(DiceMode::RollOver, 9850, U128(1000));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_SPLIT_NOT_VALID")]
+    fn preview_roulette_validates_bets() {
+        testing_env!(get_context(vec![], true));
+        let contract = Contract::default();
+        contract.preview_roulette(bets(&[("1|5", 100)]));
     }
 }
@@ -0,0 +1,481 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{U128, U64, ValidAccountId};
+use near_sdk::{env, near_bindgen, AccountId, Balance};
+use serde::{Serialize, Deserialize};
+
+use crate::*;
+
+const DAY: u64 = 86_400 * 1_000_000_000;
+// Raising or removing a limit only takes effect after this long
+pub const LIMIT_INCREASE_COOLDOWN: u64 = DAY;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitKind {
+    Deposit,
+    Loss,
+    Wager,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum LimitPeriod {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+impl LimitPeriod {
+    fn duration(&self) -> u64 {
+        match self {
+            LimitPeriod::Daily => DAY,
+            LimitPeriod::Weekly => 7 * DAY,
+            LimitPeriod::Monthly => 30 * DAY,
+        }
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+pub struct PendingLimit {
+    amount: Option<Balance>,
+    effective_at: U64,
+}
+
+// `used` counts deposits, net losses or stakes within the window starting at `window_start`
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+pub struct Limit {
+    kind: LimitKind,
+    period: LimitPeriod,
+    amount: Balance,
+    used: Balance,
+    window_start: U64,
+    pending: Option<PendingLimit>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+pub struct PendingSessionLimit {
+    max_length: Option<U64>,
+    cool_off: U64,
+    effective_at: U64,
+}
+
+// A session starts with the first bet after the last cool-off has passed
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+pub struct SessionLimit {
+    max_length: U64,
+    cool_off: U64,
+    session_start: Option<U64>,
+    pending: Option<PendingSessionLimit>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+pub struct GamingLimits {
+    limits: Vec<Limit>,
+    excluded_until: U64,
+    session: Option<SessionLimit>,
+}
+
+impl Default for GamingLimits {
+    fn default() -> Self {
+        Self {
+            limits: Vec::new(),
+            excluded_until: U64(0),
+            session: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SessionStatus {
+    in_session: bool,
+    remaining: U64,
+    cool_off_ends: Option<U64>,
+}
+
+#[near_bindgen]
+impl Contract {
+    // Passing no amount removes the limit. Lower limits apply at once, higher ones after the cooldown.
+    pub fn set_gaming_limit(&mut self, kind: LimitKind, period: LimitPeriod, amount: Option<U128>) -> GamingLimits {
+        let account_id = env::predecessor_account_id();
+        assert!(self.users.get(&account_id).is_some(), "Account Id does not exist");
+        let now = env::block_timestamp();
+        let mut gaming_limits = self.refreshed_limits(&account_id);
+        let amount = amount.map(|amount| amount.0);
+
+        match gaming_limits.limits.iter_mut().find(|limit| limit.kind == kind && limit.period == period) {
+            Some(limit) => match amount {
+                Some(amount) if amount < limit.amount => {
+                    limit.amount = amount;
+                    limit.pending = None;
+                }
+                _ => {
+                    limit.pending = Some(PendingLimit { amount, effective_at: U64(now + LIMIT_INCREASE_COOLDOWN) });
+                }
+            },
+            None => {
+                let amount = amount.expect("ERR_LIMIT_NOT_FOUND");
+                gaming_limits.limits.push(Limit { kind, period, amount, used: 0, window_start: U64(now), pending: None });
+            }
+        }
+        self.gaming_limits.insert(&account_id, &gaming_limits);
+        gaming_limits
+    }
+
+    // An exclusion can be extended but never shortened
+    pub fn self_exclude(&mut self, duration: U64) -> GamingLimits {
+        let account_id = env::predecessor_account_id();
+        assert!(self.users.get(&account_id).is_some(), "Account Id does not exist");
+        let mut gaming_limits = self.refreshed_limits(&account_id);
+        let excluded_until = env::block_timestamp().checked_add(duration.0).expect("ERR_DURATION_NOT_VALID");
+        gaming_limits.excluded_until = U64(gaming_limits.excluded_until.0.max(excluded_until));
+        self.gaming_limits.insert(&account_id, &gaming_limits);
+        gaming_limits
+    }
+
+    // Passing no length removes the session limit. Changes are not possible during a cool-off.
+    // A shorter session with a cool-off at least as long applies at once, anything else after the cooldown.
+    pub fn set_session_limit(&mut self, max_length: Option<U64>, cool_off: U64) -> GamingLimits {
+        let account_id = env::predecessor_account_id();
+        assert!(self.users.get(&account_id).is_some(), "Account Id does not exist");
+        let now = env::block_timestamp();
+        let mut gaming_limits = self.refreshed_limits(&account_id);
+        if let Some(max_length) = max_length {
+            assert!(max_length.0 > 0, "ERR_SESSION_LENGTH_NOT_VALID");
+        }
+        match gaming_limits.session.as_mut() {
+            Some(session) => {
+                assert!(session.cool_off_ends(now).is_none(), "ERR_SESSION_COOL_OFF");
+                match max_length {
+                    Some(max_length) if max_length.0 <= session.max_length.0 && cool_off.0 >= session.cool_off.0 => {
+                        session.max_length = max_length;
+                        session.cool_off = cool_off;
+                        session.pending = None;
+                    }
+                    _ => {
+                        let effective_at = U64(now + LIMIT_INCREASE_COOLDOWN);
+                        session.pending = Some(PendingSessionLimit { max_length, cool_off, effective_at });
+                    }
+                }
+            }
+            None => {
+                gaming_limits.session = max_length.map(|max_length| SessionLimit {
+                    max_length,
+                    cool_off,
+                    session_start: None,
+                    pending: None,
+                });
+            }
+        }
+        self.gaming_limits.insert(&account_id, &gaming_limits);
+        gaming_limits
+    }
+
+    pub fn get_session_status(&self, account_id: ValidAccountId) -> Option<SessionStatus> {
+        let now = env::block_timestamp();
+        self.gaming_limits.get(account_id.as_ref())?;
+        let session = self.refreshed_limits(account_id.as_ref()).session?;
+        let cool_off_ends = session.cool_off_ends(now);
+        let remaining = match session.active_start(now) {
+            Some(session_start) => session_start.saturating_add(session.max_length.0) - now,
+            None if cool_off_ends.is_some() => 0,
+            None => session.max_length.0,
+        };
+        Some(SessionStatus {
+            in_session: session.active_start(now).is_some(),
+            remaining: U64(remaining),
+            cool_off_ends: cool_off_ends.map(U64),
+        })
+    }
+
+    pub fn get_gaming_limits(&self, account_id: ValidAccountId) -> GamingLimits {
+        self.gaming_limits.get(account_id.as_ref()).unwrap_or_default()
+    }
+}
+
+impl Contract {
+    fn refreshed_limits(&self, account_id: &AccountId) -> GamingLimits {
+        let now = env::block_timestamp();
+        let mut gaming_limits = self.gaming_limits.get(account_id).unwrap_or_default();
+        gaming_limits.limits.retain(|limit| !limit.is_removed_at(now));
+        for limit in gaming_limits.limits.iter_mut() {
+            limit.refresh(now);
+        }
+        if let Some(session) = gaming_limits.session.take() {
+            gaming_limits.session = session.refreshed(now);
+        }
+        gaming_limits
+    }
+
+    fn update_limits<F: Fn(&mut Limit)>(&mut self, account_id: &AccountId, update: F) {
+        if self.gaming_limits.get(account_id).is_none() {
+            return;
+        }
+        let mut gaming_limits = self.refreshed_limits(account_id);
+        for limit in gaming_limits.limits.iter_mut() {
+            update(limit);
+        }
+        self.gaming_limits.insert(account_id, &gaming_limits);
+    }
+
+    pub(crate) fn assert_not_excluded(&self, account_id: &AccountId) {
+        if let Some(gaming_limits) = self.gaming_limits.get(account_id) {
+            assert!(env::block_timestamp() >= gaming_limits.excluded_until.0, "ERR_SELF_EXCLUDED");
+        }
+    }
+
+    pub(crate) fn check_deposit_limits(&mut self, account_id: &AccountId, amount: Balance) {
+        self.assert_not_excluded(account_id);
+        self.update_limits(account_id, |limit| {
+            if limit.kind == LimitKind::Deposit {
+                limit.consume(amount, "ERR_DEPOSIT_LIMIT_REACHED");
+            }
+        });
+    }
+
+    // A stake is rejected if losing all of it could break the loss limit
+    pub(crate) fn check_stake_limits(&mut self, account_id: &AccountId, stake: Balance) {
+        self.assert_not_excluded(account_id);
+        self.track_session(account_id);
+        self.update_limits(account_id, |limit| match limit.kind {
+            LimitKind::Wager => limit.consume(stake, "ERR_WAGER_LIMIT_REACHED"),
+            LimitKind::Loss => assert!(add_balance(limit.used, stake) <= limit.amount, "ERR_LOSS_LIMIT_REACHED"),
+            LimitKind::Deposit => {}
+        });
+    }
+
+    fn track_session(&mut self, account_id: &AccountId) {
+        if self.gaming_limits.get(account_id).is_none() {
+            return;
+        }
+        let mut gaming_limits = self.refreshed_limits(account_id);
+        if let Some(session) = gaming_limits.session.as_mut() {
+            let now = env::block_timestamp();
+            assert!(session.cool_off_ends(now).is_none(), "ERR_SESSION_COOL_OFF");
+            if session.active_start(now).is_none() {
+                session.session_start = Some(U64(now));
+                self.gaming_limits.insert(account_id, &gaming_limits);
+            }
+        }
+    }
+
+    // Winnings offset earlier losses within the same window
+    pub(crate) fn record_loss(&mut self, account_id: &AccountId, stake: Balance, total_winning: Balance) {
+        self.update_limits(account_id, |limit| {
+            if limit.kind == LimitKind::Loss {
+                limit.used = add_balance(limit.used, stake).saturating_sub(total_winning);
+            }
+        });
+    }
+}
+
+impl SessionLimit {
+    fn active_start(&self, now: u64) -> Option<u64> {
+        self.session_start.map(|start| start.0).filter(|start| now < start.saturating_add(self.max_length.0))
+    }
+
+    fn cool_off_ends(&self, now: u64) -> Option<u64> {
+        let session_end = self.session_start?.0.saturating_add(self.max_length.0);
+        let cool_off_ends = session_end.saturating_add(self.cool_off.0);
+        if now >= session_end && now < cool_off_ends {
+            Some(cool_off_ends)
+        } else {
+            None
+        }
+    }
+
+    // Applies a pending change once its cooldown has passed, which may remove the limit
+    fn refreshed(mut self, now: u64) -> Option<Self> {
+        match self.pending.take() {
+            Some(pending) if now >= pending.effective_at.0 => {
+                let max_length = pending.max_length?;
+                Some(Self { max_length, cool_off: pending.cool_off, ..self })
+            }
+            pending => Some(Self { pending, ..self }),
+        }
+    }
+}
+
+impl Limit {
+    fn is_removed_at(&self, now: u64) -> bool {
+        matches!(&self.pending, Some(PendingLimit { amount: None, effective_at }) if now >= effective_at.0)
+    }
+
+    // Applies a pending increase once its cooldown has passed and opens a new window once the last one ended
+    fn refresh(&mut self, now: u64) {
+        if let Some(pending) = self.pending.take() {
+            match pending.amount {
+                Some(amount) if now >= pending.effective_at.0 => self.amount = amount,
+                _ => self.pending = Some(pending),
+            }
+        }
+        if now >= self.window_start.0 + self.period.duration() {
+            self.window_start = U64(now);
+            self.used = 0;
+        }
+    }
+
+    fn consume(&mut self, amount: Balance, error: &str) {
+        self.used = add_balance(self.used, amount);
+        assert!(self.used <= self.amount, "{}", error);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{get_context, set_context};
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+    use std::convert::TryInto;
+
+    fn setup() -> Contract {
+        testing_env!(get_context(vec![], false));
+        let mut contract = Contract { house: 100_000, ..Default::default() };
+        set_context("carol_near", 0, 0, 1000);
+        contract.deposit(None);
+        contract
+    }
+
+    #[test]
+    fn decreases_apply_now_and_increases_after_cooldown() {
+        let mut contract = setup();
+        contract.set_gaming_limit(LimitKind::Deposit, LimitPeriod::Daily, Some(U128(500)));
+        contract.set_gaming_limit(LimitKind::Deposit, LimitPeriod::Daily, Some(U128(300)));
+        let limits = contract.set_gaming_limit(LimitKind::Deposit, LimitPeriod::Daily, Some(U128(800)));
+        assert_eq!(300, limits.limits[0].amount);
+
+        set_context("carol_near", 0, LIMIT_INCREASE_COOLDOWN - 1, 300);
+        contract.deposit(None);
+        set_context("carol_near", 0, LIMIT_INCREASE_COOLDOWN, 500);
+        // The window rolled over and the increase is now active
+        contract.deposit(None);
+        let limits = contract.get_gaming_limits("carol_near".try_into().unwrap());
+        assert_eq!(800, limits.limits[0].amount);
+        assert_eq!(500, limits.limits[0].used);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DEPOSIT_LIMIT_REACHED")]
+    fn deposit_limit_is_enforced() {
+        let mut contract = setup();
+        contract.set_gaming_limit(LimitKind::Deposit, LimitPeriod::Weekly, Some(U128(500)));
+        set_context("carol_near", 0, DAY, 300);
+        contract.deposit(None);
+        set_context("carol_near", 0, 2 * DAY, 300);
+        contract.deposit(None);
+    }
+
+    #[test]
+    fn losses_net_against_winnings() {
+        let mut contract = setup();
+        contract.set_gaming_limit(LimitKind::Loss, LimitPeriod::Daily, Some(U128(300)));
+        let mut account = contract.users.get(&"carol_near".to_string()).unwrap();
+        contract.settle(&mut account, "dice", 200, 0);
+        let mut account = contract.users.get(&"carol_near".to_string()).unwrap();
+        contract.settle(&mut account, "dice", 100, 250);
+        let limits = contract.get_gaming_limits("carol_near".try_into().unwrap());
+        assert_eq!(50, limits.limits[0].used);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_LOSS_LIMIT_REACHED")]
+    fn stake_that_could_break_the_loss_limit_is_rejected() {
+        let mut contract = setup();
+        contract.set_gaming_limit(LimitKind::Loss, LimitPeriod::Monthly, Some(U128(300)));
+        contract.play_dice(DiceMode::RollUnder, 5000, U128(301), None);
+    }
+
+    #[test]
+    fn self_exclusion_blocks_play_but_not_withdraw() {
+        let mut contract = setup();
+        contract.self_exclude(U64(DAY));
+        assert_eq!(400, contract.withdraw(600));
+
+        set_context("carol_near", 0, DAY, 0);
+        contract.play_dice(DiceMode::RollUnder, 5000, U128(100), None);
+    }
+
+    #[test]
+    fn session_ends_in_a_cool_off() {
+        let mut contract = setup();
+        contract.set_session_limit(Some(U64(3_600)), U64(600));
+        let status = contract.get_session_status("carol_near".try_into().unwrap()).unwrap();
+        assert!(!status.in_session);
+        assert_eq!(3_600, status.remaining.0);
+
+        set_context("carol_near", 0, 1_000, 0);
+        contract.play_dice(DiceMode::RollUnder, 5000, U128(100), None);
+        set_context("carol_near", 0, 4_000, 0);
+        contract.play_dice(DiceMode::RollUnder, 5000, U128(100), None);
+        let status = contract.get_session_status("carol_near".try_into().unwrap()).unwrap();
+        assert!(status.in_session);
+        assert_eq!(600, status.remaining.0);
+
+        set_context("carol_near", 0, 4_700, 0);
+        let status = contract.get_session_status("carol_near".try_into().unwrap()).unwrap();
+        assert_eq!(0, status.remaining.0);
+        assert_eq!(Some(U64(5_200)), status.cool_off_ends);
+
+        // A new session starts once the cool-off has passed
+        set_context("carol_near", 0, 5_200, 0);
+        contract.play_dice(DiceMode::RollUnder, 5000, U128(100), None);
+        let status = contract.get_session_status("carol_near".try_into().unwrap()).unwrap();
+        assert_eq!(3_600, status.remaining.0);
+    }
+
+    #[test]
+    fn session_limit_is_loosened_only_after_cooldown() {
+        let mut contract = setup();
+        contract.set_session_limit(Some(U64(3_600)), U64(600));
+        // Tighter on both counts applies at once
+        let limits = contract.set_session_limit(Some(U64(1_800)), U64(1_200));
+        let session = limits.session.unwrap();
+        assert_eq!((1_800, 1_200), (session.max_length.0, session.cool_off.0));
+
+        // A longer session, a shorter cool-off or a removal waits for the cooldown
+        let limits = contract.set_session_limit(Some(U64(7_200)), U64(1_200));
+        assert_eq!(1_800, limits.session.unwrap().max_length.0);
+        let limits = contract.set_session_limit(Some(U64(1_800)), U64(0));
+        assert_eq!(1_200, limits.session.unwrap().cool_off.0);
+        contract.set_session_limit(None, U64(0));
+        set_context("carol_near", 0, LIMIT_INCREASE_COOLDOWN - 1, 0);
+        assert!(contract.get_gaming_limits("carol_near".try_into().unwrap()).session.is_some());
+        assert_eq!(1_800, contract.get_session_status("carol_near".try_into().unwrap()).unwrap().remaining.0);
+
+        set_context("carol_near", 0, LIMIT_INCREASE_COOLDOWN, 0);
+        assert!(contract.get_session_status("carol_near".try_into().unwrap()).is_none());
+        contract.play_dice(DiceMode::RollUnder, 5000, U128(100), None);
+        assert!(contract.get_gaming_limits("carol_near".try_into().unwrap()).session.is_none());
+    }
+
+    #[test]
+    fn huge_session_length_does_not_overflow() {
+        let mut contract = setup();
+        contract.set_session_limit(Some(U64(u64::MAX)), U64(u64::MAX));
+        set_context("carol_near", 0, 1_000, 0);
+        contract.play_dice(DiceMode::RollUnder, 5000, U128(100), None);
+        let status = contract.get_session_status("carol_near".try_into().unwrap()).unwrap();
+        assert!(status.in_session);
+        assert_eq!(u64::MAX - 1_000, status.remaining.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_SESSION_COOL_OFF")]
+    fn bets_are_rejected_during_cool_off() {
+        let mut contract = setup();
+        contract.set_session_limit(Some(U64(3_600)), U64(600));
+        contract.play_dice(DiceMode::RollUnder, 5000, U128(100), None);
+        set_context("carol_near", 0, 3_600, 0);
+        contract.play_dice(DiceMode::RollUnder, 5000, U128(100), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_SELF_EXCLUDED")]
+    fn excluded_account_cannot_play() {
+        let mut contract = setup();
+        contract.self_exclude(U64(DAY));
+        set_context("carol_near", 0, DAY - 1, 0);
+        contract.play_dice(DiceMode::RollUnder, 5000, U128(100), None);
+    }
+}
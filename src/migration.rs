@@ -0,0 +1,76 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::UnorderedMap;
+use near_sdk::{env, near_bindgen, AccountId, Balance};
+
+use crate::*;
+
+// State layout of the deployed contract before the game modules were added
+#[derive(BorshDeserialize, BorshSerialize)]
+struct OldAccount {
+    account_id: AccountId,
+    deposit: Balance,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+struct OldContract {
+    house: Balance,
+    users: UnorderedMap<AccountId, OldAccount>,
+}
+
+#[near_bindgen]
+impl Contract {
+    // Called once with the upgraded code. Accounts are rewritten under the same prefix with every
+    // new field empty, and the owner becomes the contract account, as for a fresh deployment.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let mut old: OldContract = env::state_read().expect("ERR_NO_STATE");
+        let accounts = old.users.to_vec();
+        old.users.clear();
+
+        let mut contract = Contract { house: old.house, ..Default::default() };
+        for (account_id, old_account) in accounts {
+            let account = Account {
+                account_id: old_account.account_id,
+                deposit: old_account.deposit,
+                referrer: None,
+                referral_rewards: 0,
+                referred_accounts: 0,
+                referral_earned: 0,
+                wagered: 0,
+                rakeback: 0,
+                bonus: 0,
+                bonus_wagering: 0,
+            };
+            contract.users.insert(&account_id, &account);
+        }
+        contract
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::get_context;
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn migrate_keeps_the_house_and_deposits() {
+        testing_env!(get_context(vec![], false));
+        let mut old = OldContract { house: 700, users: UnorderedMap::new(b"r".to_vec()) };
+        for (account_id, deposit) in [("carol_near", 300), ("dave_near", 50)].iter() {
+            old.users.insert(&account_id.to_string(), &OldAccount { account_id: account_id.to_string(), deposit: *deposit });
+        }
+        env::state_write(&old);
+
+        let contract = Contract::migrate();
+        assert_eq!(700, contract.house);
+        assert_eq!(2, contract.users.len());
+        let account = contract.users.get(&"carol_near".to_string()).unwrap();
+        assert_eq!(300, account.deposit);
+        assert_eq!(0, account.wagered);
+        assert!(account.referrer.is_none());
+    }
+}
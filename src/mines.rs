@@ -0,0 +1,265 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{U128, U64, ValidAccountId};
+use near_sdk::{env, near_bindgen, AccountId, Balance, BlockHeight};
+use serde::{Serialize, Deserialize};
+
+use crate::payout::{combinations, Multiplier, BASIS_POINTS};
+use crate::*;
+
+const TILES: u8 = 25;
+pub const DEFAULT_HOUSE_EDGE_BPS: u32 = 100;
+pub const DEFAULT_TIMEOUT: BlockHeight = 600;
+
+// No layout is kept or committed, since contract storage can be read over RPC and a stored
+// secret would give the mines away. Each reveal draws fresh randomness instead: with `revealed`
+// tiles safe so far, the picked tile is a mine with probability mines / (25 - revealed), the
+// same as for a layout fixed upfront. There is no layout to show once the game is over.
+// Tiles are numbered 0-24 row by row and `revealed` is a bitmask of them. The house keeps the
+// current cash-out value reserved while the game is open.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct MinesGame {
+    bet: Balance,
+    bonus_stake: Balance,
+    placed_at: U64,
+    mines: u8,
+    revealed: u32,
+    multiplier: Multiplier,
+    last_action_block: BlockHeight,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MinesResult {
+    account_id: AccountId,
+    mines: u8,
+    revealed: Vec<u8>,
+    multiplier: Multiplier,
+    active: bool,
+    total_winning: Balance,
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn mines_start(&mut self, bet: U128, mines: u8) -> MinesResult {
+        assert!(bet.0 > 0, "ERR_ZERO_BET");
+        assert!((1..TILES).contains(&mines), "ERR_MINES_NOT_VALID");
+        let account_id = env::predecessor_account_id();
+        if let Some(game) = self.mines_games.get(&account_id) {
+            assert!(self.is_mines_expired(&game), "ERR_GAME_IN_PROGRESS");
+            self.cashout_mines(account_id.clone(), game);
+        }
+
+        let mut account = self.users.get(&account_id).expect("Account Id does not exist");
+        let bonus_stake = self.collect_stake(&mut account, bet.0);
+        self.users.insert(&account_id, &account);
+        self.reserve_house(bet.0);
+
+        let game = MinesGame {
+            bet: bet.0,
+            bonus_stake,
+            placed_at: U64(env::block_timestamp()),
+            mines,
+            revealed: 0,
+            multiplier: Multiplier::whole(1),
+            last_action_block: env::block_index(),
+        };
+        self.mines_games.insert(&account_id, &game);
+        mines_result(account_id, &game, true, 0)
+    }
+
+    pub fn mines_reveal(&mut self, tile: u8) -> MinesResult {
+        assert!(tile < TILES, "ERR_TILE_NOT_VALID");
+        let (account_id, mut game) = self.active_mines_game();
+        assert_eq!(game.revealed & 1 << tile, 0, "ERR_TILE_ALREADY_REVEALED");
+
+        let safe_reveals = game.revealed.count_ones() as u8 + 1;
+        let multiplier = mines_multiplier(game.mines, safe_reveals, self.mines_house_edge_bps);
+        self.assert_exposure(multiplier.apply(game.bet), game.bet);
+
+        let hidden_tiles = (TILES - safe_reveals + 1) as u32;
+        let hit = self.next_random_number() % hidden_tiles < game.mines as u32;
+        game.revealed |= 1 << tile;
+        self.release_house(game.multiplier.apply(game.bet));
+        if hit {
+            game.multiplier = Multiplier::ZERO;
+            return self.cashout_mines(account_id, game);
+        }
+
+        game.multiplier = multiplier;
+        game.last_action_block = env::block_index();
+        self.reserve_house(multiplier.apply(game.bet));
+        if safe_reveals == TILES - game.mines {
+            return self.cashout_mines(account_id, game);
+        }
+        self.mines_games.insert(&account_id, &game);
+        mines_result(account_id, &game, true, 0)
+    }
+
+    // At least one tile must be revealed, so a game cannot be opened and closed at 1x for free
+    pub fn mines_cashout(&mut self) -> MinesResult {
+        let (account_id, game) = self.active_mines_game();
+        assert!(game.revealed != 0, "ERR_NO_REVEAL");
+        self.cashout_mines(account_id, game)
+    }
+
+    // Anyone can close a timed out game, which cashes it out at its current multiplier
+    pub fn mines_close_expired(&mut self, account_id: ValidAccountId) -> MinesResult {
+        let account_id: AccountId = account_id.into();
+        let game = self.mines_games.get(&account_id).expect("ERR_NO_ACTIVE_GAME");
+        assert!(self.is_mines_expired(&game), "ERR_GAME_NOT_EXPIRED");
+        self.cashout_mines(account_id, game)
+    }
+
+    pub fn get_mines_game(&self, account_id: ValidAccountId) -> Option<MinesResult> {
+        let account_id: AccountId = account_id.into();
+        self.mines_games.get(&account_id).map(|game| mines_result(account_id, &game, true, 0))
+    }
+
+    pub fn get_mines_multiplier(&self, mines: u8, safe_reveals: u8) -> Multiplier {
+        assert!((1..TILES).contains(&mines) && safe_reveals <= TILES - mines, "ERR_MINES_NOT_VALID");
+        mines_multiplier(mines, safe_reveals, self.mines_house_edge_bps)
+    }
+
+    pub fn get_mines_timeout(&self) -> BlockHeight {
+        self.mines_timeout
+    }
+
+    pub fn set_mines_timeout(&mut self, blocks: BlockHeight) {
+        self.assert_owner();
+        self.mines_timeout = blocks;
+    }
+
+    pub fn set_mines_house_edge(&mut self, house_edge_bps: u32) {
+        self.assert_owner();
+        assert!((house_edge_bps as u128) < BASIS_POINTS, "ERR_HOUSE_EDGE_NOT_VALID");
+        self.mines_house_edge_bps = house_edge_bps;
+    }
+}
+
+impl Contract {
+    fn is_mines_expired(&self, game: &MinesGame) -> bool {
+        match game.last_action_block.checked_add(self.mines_timeout) {
+            Some(deadline) => env::block_index() > deadline,
+            None => false,
+        }
+    }
+
+    fn active_mines_game(&self) -> (AccountId, MinesGame) {
+        let account_id = env::predecessor_account_id();
+        let game = self.mines_games.get(&account_id).expect("ERR_NO_ACTIVE_GAME");
+        assert!(!self.is_mines_expired(&game), "ERR_GAME_EXPIRED");
+        (account_id, game)
+    }
+
+    fn cashout_mines(&mut self, account_id: AccountId, game: MinesGame) -> MinesResult {
+        let total_winning = game.multiplier.apply(game.bet);
+        self.release_house(total_winning);
+        let mut account = self.users.get(&account_id).expect("Account Id does not exist");
+        let void = game.revealed == 0;
+        self.pay_winning(&mut account, "mines", game.bet, game.bonus_stake, total_winning, game.placed_at.0, void);
+        self.users.insert(&account_id, &account);
+        self.mines_games.remove(&account_id);
+        mines_result(account_id, &game, false, total_winning)
+    }
+}
+
+fn tiles_of(mask: u32) -> Vec<u8> {
+    (0..TILES).filter(|tile| mask & 1 << tile != 0).collect()
+}
+
+fn mines_result(account_id: AccountId, game: &MinesGame, active: bool, total_winning: Balance) -> MinesResult {
+    MinesResult {
+        account_id,
+        mines: game.mines,
+        revealed: tiles_of(game.revealed),
+        multiplier: game.multiplier,
+        active,
+        total_winning,
+    }
+}
+
+// The chance of `safe_reveals` picks all missing the mines is C(25 - mines, k) / C(25, k)
+fn mines_multiplier(mines: u8, safe_reveals: u8, house_edge_bps: u32) -> Multiplier {
+    let safe_tiles = (TILES - mines) as u128;
+    let reveals = safe_reveals as u128;
+    Multiplier::from_bps(BASIS_POINTS - house_edge_bps as u128)
+        .over_probability(combinations(safe_tiles, reveals), combinations(TILES as u128, reveals))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{get_context, set_context};
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+    use std::convert::TryInto;
+
+    fn setup() -> Contract {
+        let mut context = get_context(vec![], false);
+        context.attached_deposit = 1000;
+        testing_env!(context);
+        let mut contract = Contract { house: 10u128.pow(9), ..Default::default() };
+        contract.deposit(None);
+        contract
+    }
+
+    #[test]
+    fn multipliers_follow_the_combinatorics() {
+        assert_eq!(Multiplier::from_bps(10_312), mines_multiplier(1, 1, 100));
+        assert_eq!(Multiplier::from_bps(247_500), mines_multiplier(1, 24, 100));
+        assert_eq!(Multiplier::from_bps(247_500), mines_multiplier(24, 1, 100));
+        // C(25, 2) / C(22, 2) = 300 / 231
+        assert_eq!(Multiplier::from_bps(12_857), mines_multiplier(3, 2, 100));
+    }
+
+    #[test]
+    fn reveal_then_cashout() {
+        // Under a zero seed the first draw misses the single mine
+        let mut contract = setup();
+        let started = contract.mines_start(U128(100), 1);
+        assert!(started.active);
+
+        let revealed = contract.mines_reveal(7);
+        assert!(revealed.active);
+        assert_eq!(mines_multiplier(1, 1, 100), revealed.multiplier);
+
+        let result = contract.mines_cashout();
+        assert_eq!(revealed.multiplier.apply(100), result.total_winning);
+        assert_eq!(900 + result.total_winning, contract.users.get(&"carol_near".to_string()).unwrap().deposit);
+        assert_eq!(vec![7], result.revealed);
+    }
+
+    #[test]
+    fn hitting_a_mine_loses_the_stake() {
+        // Under a zero seed the first draw lands on one of the 24 mines
+        let mut contract = setup();
+        contract.mines_start(U128(100), 24);
+        let result = contract.mines_reveal(3);
+        assert!(!result.active);
+        assert_eq!(0, result.total_winning);
+        assert_eq!(vec![3], result.revealed);
+        assert_eq!(900, contract.users.get(&"carol_near".to_string()).unwrap().deposit);
+        assert!(contract.mines_games.get(&"carol_near".to_string()).is_none());
+    }
+
+    #[test]
+    fn expired_game_is_cashed_out() {
+        let mut contract = setup();
+        contract.mines_start(U128(100), 3);
+        assert_eq!(100, contract.house_liability);
+
+        set_context("dave_near", DEFAULT_TIMEOUT + 1, 0, 0);
+        let result = contract.mines_close_expired("carol_near".to_string().try_into().unwrap());
+        assert_eq!(100, result.total_winning);
+        assert_eq!(0, contract.house_liability);
+        assert_eq!(1000, contract.users.get(&"carol_near".to_string()).unwrap().deposit);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NO_REVEAL")]
+    fn cashout_needs_a_reveal() {
+        let mut contract = setup();
+        contract.mines_start(U128(100), 3);
+        contract.mines_cashout();
+    }
+}
@@ -0,0 +1,192 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{Base64VecU8, ValidAccountId};
+use near_sdk::{assert_one_yocto, env, ext_contract, near_bindgen, AccountId, Balance, Gas, PromiseOrValue, PromiseResult};
+use serde::{Serialize, Deserialize};
+
+use crate::events::emit_standard_event;
+use crate::*;
+
+pub type TokenId = String;
+
+const NO_DEPOSIT: Balance = 0;
+const GAS_FOR_RESOLVE_TRANSFER: Gas = 10_000_000_000_000;
+const GAS_FOR_NFT_TRANSFER_CALL: Gas = 25_000_000_000_000 + GAS_FOR_RESOLVE_TRANSFER;
+
+// NEP-177 contract metadata
+#[derive(Serialize, Deserialize)]
+pub struct NFTContractMetadata {
+    pub(crate) spec: String,
+    name: String,
+    symbol: String,
+    icon: Option<String>,
+    base_uri: Option<String>,
+    reference: Option<String>,
+    reference_hash: Option<Base64VecU8>,
+}
+
+// NEP-177 token metadata, badges only fill in the title, description and issue time
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Default)]
+pub struct TokenMetadata {
+    pub(crate) title: Option<String>,
+    pub(crate) description: Option<String>,
+    pub(crate) media: Option<String>,
+    pub(crate) media_hash: Option<Base64VecU8>,
+    pub(crate) copies: Option<u64>,
+    pub(crate) issued_at: Option<String>,
+    pub(crate) expires_at: Option<String>,
+    pub(crate) starts_at: Option<String>,
+    pub(crate) updated_at: Option<String>,
+    pub(crate) extra: Option<String>,
+    pub(crate) reference: Option<String>,
+    pub(crate) reference_hash: Option<Base64VecU8>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct StoredToken {
+    owner_id: AccountId,
+    metadata: TokenMetadata,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Token {
+    pub(crate) token_id: TokenId,
+    pub(crate) owner_id: AccountId,
+    pub(crate) metadata: TokenMetadata,
+}
+
+#[derive(Serialize)]
+struct NftMintLog {
+    owner_id: AccountId,
+    token_ids: Vec<TokenId>,
+}
+
+#[derive(Serialize)]
+struct NftTransferLog {
+    old_owner_id: AccountId,
+    new_owner_id: AccountId,
+    token_ids: Vec<TokenId>,
+    memo: Option<String>,
+}
+
+#[ext_contract(ext_nft_receiver)]
+pub trait NonFungibleTokenReceiver {
+    fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: TokenId,
+        msg: String,
+    ) -> PromiseOrValue<bool>;
+}
+
+#[ext_contract(ext_self)]
+pub trait NonFungibleTokenResolver {
+    fn nft_resolve_transfer(&mut self, previous_owner_id: AccountId, receiver_id: AccountId, token_id: TokenId) -> bool;
+}
+
+#[near_bindgen]
+impl Contract {
+    // Approvals are not supported, so `approval_id` must be empty
+    #[payable]
+    pub fn nft_transfer(&mut self, receiver_id: ValidAccountId, token_id: TokenId, approval_id: Option<u64>, memo: Option<String>) {
+        assert_one_yocto();
+        assert!(approval_id.is_none(), "ERR_APPROVALS_NOT_SUPPORTED");
+        let sender_id = env::predecessor_account_id();
+        self.transfer_nft(&sender_id, receiver_id.as_ref(), &token_id, memo);
+    }
+
+    #[payable]
+    pub fn nft_transfer_call(
+        &mut self,
+        receiver_id: ValidAccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        assert_one_yocto();
+        assert!(approval_id.is_none(), "ERR_APPROVALS_NOT_SUPPORTED");
+        assert!(env::prepaid_gas() > GAS_FOR_NFT_TRANSFER_CALL, "ERR_NOT_ENOUGH_GAS");
+        let sender_id = env::predecessor_account_id();
+        self.transfer_nft(&sender_id, receiver_id.as_ref(), &token_id, memo);
+
+        ext_nft_receiver::nft_on_transfer(
+            sender_id.clone(),
+            sender_id.clone(),
+            token_id.clone(),
+            msg,
+            receiver_id.as_ref(),
+            NO_DEPOSIT,
+            env::prepaid_gas() - GAS_FOR_NFT_TRANSFER_CALL,
+        )
+        .then(ext_self::nft_resolve_transfer(
+            sender_id,
+            receiver_id.into(),
+            token_id,
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            GAS_FOR_RESOLVE_TRANSFER,
+        ))
+        .into()
+    }
+
+    // Returns the token to its previous owner if the receiver asked for it or the call failed
+    #[private]
+    pub fn nft_resolve_transfer(&mut self, previous_owner_id: AccountId, receiver_id: AccountId, token_id: TokenId) -> bool {
+        let must_revert = match env::promise_result(0) {
+            PromiseResult::Successful(value) => serde_json::from_slice::<bool>(&value).unwrap_or(true),
+            _ => true,
+        };
+        if !must_revert {
+            return true;
+        }
+
+        match self.nft_tokens.get(&token_id) {
+            Some(token) if token.owner_id == receiver_id => {
+                self.transfer_nft(&receiver_id, &previous_owner_id, &token_id, None);
+                false
+            }
+            _ => true,
+        }
+    }
+
+    pub fn nft_token(&self, token_id: TokenId) -> Option<Token> {
+        self.nft_tokens.get(&token_id).map(|token| Token { token_id, owner_id: token.owner_id, metadata: token.metadata })
+    }
+
+    pub fn nft_metadata(&self) -> NFTContractMetadata {
+        NFTContractMetadata {
+            spec: "nft-1.0.0".to_string(),
+            name: "Sic Bo Achievements".to_string(),
+            symbol: "SICBO".to_string(),
+            icon: None,
+            base_uri: None,
+            reference: None,
+            reference_hash: None,
+        }
+    }
+}
+
+impl Contract {
+    pub(crate) fn mint_nft(&mut self, token_id: TokenId, owner_id: AccountId, metadata: TokenMetadata) {
+        assert!(self.nft_tokens.get(&token_id).is_none(), "ERR_TOKEN_EXISTS");
+        self.nft_tokens.insert(&token_id, &StoredToken { owner_id: owner_id.clone(), metadata });
+        emit_standard_event("nep171", "1.0.0", "nft_mint", vec![NftMintLog { owner_id, token_ids: vec![token_id] }]);
+    }
+
+    fn transfer_nft(&mut self, sender_id: &str, receiver_id: &str, token_id: &str, memo: Option<String>) {
+        let token_id = token_id.to_string();
+        let mut token = self.nft_tokens.get(&token_id).expect("ERR_TOKEN_NOT_FOUND");
+        assert_eq!(token.owner_id, sender_id, "ERR_NOT_TOKEN_OWNER");
+        assert_ne!(token.owner_id, receiver_id, "ERR_SAME_OWNER");
+        token.owner_id = receiver_id.to_string();
+        self.nft_tokens.insert(&token_id, &token);
+        let log = NftTransferLog {
+            old_owner_id: sender_id.to_string(),
+            new_owner_id: receiver_id.to_string(),
+            token_ids: vec![token_id],
+            memo,
+        };
+        emit_standard_event("nep171", "1.0.0", "nft_transfer", vec![log]);
+    }
+}
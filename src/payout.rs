@@ -0,0 +1,164 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::Balance;
+use serde::{Serialize, Deserialize};
+
+pub const BASIS_POINTS: u128 = 10_000;
+
+// Fixed-point payout multiplier in basis points of the stake, e.g. 2.5x is 25_000.
+// Every conversion rounds down, so any rounding dust stays with the house.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(transparent)]
+pub struct Multiplier(u128);
+
+impl Multiplier {
+    pub const ZERO: Multiplier = Multiplier(0);
+
+    pub const fn from_bps(bps: u128) -> Self {
+        Multiplier(bps)
+    }
+
+    pub const fn whole(times: u32) -> Self {
+        Multiplier(times as u128 * BASIS_POINTS)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0 == 0
+    }
+
+    // Scales this return by the inverse of a win probability of `winning` out of `outcomes`
+    pub fn over_probability(&self, winning: u128, outcomes: u128) -> Self {
+        assert!(winning > 0, "ERR_ZERO_PROBABILITY");
+        Multiplier(self.0.checked_mul(outcomes).expect("ERR_MULTIPLIER_OVERFLOW") / winning)
+    }
+
+    // Compounds two multipliers, e.g. consecutive steps of a streak
+    pub fn times(&self, other: Multiplier) -> Self {
+        Multiplier(self.0.checked_mul(other.0).expect("ERR_MULTIPLIER_OVERFLOW") / BASIS_POINTS)
+    }
+
+    // Total returned for a stake, rounded down to the yoctoNEAR
+    pub fn apply(&self, stake: Balance) -> Balance {
+        stake.checked_mul(self.0).expect("ERR_PAYOUT_OVERFLOW") / BASIS_POINTS
+    }
+}
+
+pub fn combinations(n: u128, k: u128) -> u128 {
+    if k > n {
+        return 0;
+    }
+    (0..k).fold(1, |total, i| total * (n - i) / (i + 1))
+}
+
+// a * b / c rounded down, with the product kept at 256 bits so large balances can't overflow it
+pub fn mul_div(a: u128, b: u128, c: u128) -> u128 {
+    assert!(c > 0, "ERR_DIVISION_BY_ZERO");
+    const MASK: u128 = u64::MAX as u128;
+    let (a_hi, a_lo) = (a >> 64, a & MASK);
+    let (b_hi, b_lo) = (b >> 64, b & MASK);
+    let (low, cross_1, cross_2, high) = (a_lo * b_lo, a_lo * b_hi, a_hi * b_lo, a_hi * b_hi);
+    let middle = (low >> 64) + (cross_1 & MASK) + (cross_2 & MASK);
+    let product_lo = (low & MASK) | (middle & MASK) << 64;
+    let product_hi = high + (cross_1 >> 64) + (cross_2 >> 64) + (middle >> 64);
+    assert!(product_hi < c, "ERR_MUL_DIV_OVERFLOW");
+
+    // Long division of the 256-bit product, one bit of the low half at a time
+    let mut remainder = product_hi;
+    let mut quotient: u128 = 0;
+    for bit in (0..128).rev() {
+        let carry = remainder >> 127;
+        remainder = remainder << 1 | (product_lo >> bit & 1);
+        quotient <<= 1;
+        if carry == 1 || remainder >= c {
+            remainder = remainder.wrapping_sub(c);
+            quotient |= 1;
+        }
+    }
+    quotient
+}
+
+// Exact expected return of a paytable in basis points, rounded down, where entry `i` is hit
+// in `weights[i]` out of `outcomes` equally likely outcomes
+pub fn expected_return_bps(paytable: &[Multiplier], weights: &[u128], outcomes: u128) -> u128 {
+    let weighted: u128 = paytable
+        .iter()
+        .zip(weights)
+        .map(|(multiplier, weight)| weight.checked_mul(multiplier.0).expect("ERR_MULTIPLIER_OVERFLOW"))
+        .sum();
+    weighted / outcomes
+}
+
+// The largest stake a bankroll should cover on a paytable, as `fraction_bps` of the Kelly
+// stake. Seen from the house, a unit stake returns x = 1 - multiplier, and the Kelly share of
+// the bankroll is approximated by mean(x) / variance(x), capped at the whole bankroll. With
+// x in basis points, S1 = sum(w * x) and S2 = sum(w * x^2) over `weights` summing to N, the
+// share is S1 * BASIS_POINTS / (S2 - S1^2 / N), applied without rounding in between.
+pub fn kelly_max_bet(bankroll: u128, fraction_bps: u32, paytable: &[Multiplier], weights: &[u128]) -> u128 {
+    let outcomes: u128 = weights.iter().sum();
+    let (sum, sum_squares) = paytable.iter().zip(weights).fold((0i128, 0u128), |(sum, sum_squares), (multiplier, weight)| {
+        let house_return = BASIS_POINTS as i128 - multiplier.0 as i128;
+        let weighted = house_return.checked_mul(*weight as i128).expect("ERR_MULTIPLIER_OVERFLOW");
+        let squared = weighted.unsigned_abs().checked_mul(house_return.unsigned_abs()).expect("ERR_MULTIPLIER_OVERFLOW");
+        (sum + weighted, sum_squares.checked_add(squared).expect("ERR_MULTIPLIER_OVERFLOW"))
+    });
+    if sum <= 0 {
+        return 0;
+    }
+    let sum = sum as u128;
+    let max_bet = mul_div(bankroll, fraction_bps as u128, BASIS_POINTS);
+    let variance = sum_squares - mul_div(sum, sum, outcomes);
+    let edge = sum * BASIS_POINTS;
+    if edge >= variance {
+        return max_bet;
+    }
+    mul_div(max_bet, edge, variance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_rounds_down_to_the_yocto() {
+        let multiplier = Multiplier::from_bps(9_850).over_probability(498, 1_000);
+        assert_eq!(Multiplier::from_bps(19_779), multiplier);
+        assert_eq!(1, multiplier.apply(1));
+        assert_eq!(197_790_000_000_000_000_000_000, multiplier.apply(10u128.pow(23)));
+        assert_eq!(3, Multiplier::from_bps(39_999).apply(1));
+    }
+
+    #[test]
+    fn over_probability_rounds_down() {
+        let multiplier = Multiplier::from_bps(9_850).over_probability(2474, 10_000);
+        assert_eq!(Multiplier::from_bps(39_814), multiplier);
+        assert_eq!(3_981_400_000_000_000_000_000_000, multiplier.apply(10u128.pow(24)));
+    }
+
+    #[test]
+    fn mul_div_keeps_the_full_product() {
+        assert_eq!(7, mul_div(10, 15, 20));
+        let near = 10u128.pow(24);
+        assert_eq!(3 * 10u128.pow(33), mul_div(10u128.pow(9) * near, 3 * 10u128.pow(9) * near, 10u128.pow(9) * near));
+        assert_eq!(u128::MAX / 3, mul_div(u128::MAX, u128::MAX / 3, u128::MAX));
+    }
+
+    #[test]
+    fn kelly_scales_with_edge_and_variance() {
+        // An even money bet winning 49.5% of the time: edge 1%, Kelly 1 / 0.9999 of a percent
+        let even_money = [Multiplier::whole(2), Multiplier::ZERO];
+        assert_eq!(100, kelly_max_bet(10_000, 10_000, &even_money, &[495, 505]));
+        assert_eq!(50, kelly_max_bet(10_000, 5_000, &even_money, &[495, 505]));
+        // The same edge spread over a longshot allows a far smaller stake
+        assert_eq!(103, kelly_max_bet(1_000_000, 10_000, &[Multiplier::whole(99), Multiplier::ZERO], &[1, 99]));
+        // A straight-up roulette bet keeps the fraction of its 16.78 basis points
+        let straight = [Multiplier::whole(35), Multiplier::ZERO];
+        assert_eq!(167_800, kelly_max_bet(10u128.pow(8), 10_000, &straight, &[1, 36]));
+        assert_eq!(0, kelly_max_bet(10_000, 10_000, &even_money, &[1, 1]));
+        assert_eq!(10_000, kelly_max_bet(10_000, 10_000, &[Multiplier::whole(1), Multiplier::ZERO], &[1, 1]));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PAYOUT_OVERFLOW")]
+    fn apply_overflow_panics() {
+        Multiplier::whole(180).apply(u128::MAX / 2);
+    }
+}
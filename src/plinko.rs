@@ -0,0 +1,168 @@
+use near_sdk::{env, near_bindgen, AccountId, Balance};
+use near_sdk::json_types::U128;
+use serde::{Serialize, Deserialize};
+
+use crate::payout::{combinations, Multiplier};
+use crate::*;
+
+const MIN_ROWS: u8 = 8;
+const MAX_ROWS: u8 = 16;
+
+// Multipliers in tenths by slot, one row per board size from 8 to 16 rows
+const LOW_RISK_TABLES: [&[u32]; 9] = [
+    &[56, 21, 11, 10, 5, 10, 11, 21, 56],
+    &[56, 20, 16, 10, 7, 7, 10, 16, 20, 56],
+    &[89, 30, 14, 11, 10, 5, 10, 11, 14, 30, 89],
+    &[84, 30, 19, 13, 10, 7, 7, 10, 13, 19, 30, 84],
+    &[100, 30, 16, 14, 11, 10, 5, 10, 11, 14, 16, 30, 100],
+    &[81, 40, 30, 19, 12, 9, 7, 7, 9, 12, 19, 30, 40, 81],
+    &[71, 40, 19, 14, 13, 11, 10, 5, 10, 11, 13, 14, 19, 40, 71],
+    &[150, 80, 30, 20, 15, 11, 10, 7, 7, 10, 11, 15, 20, 30, 80, 150],
+    &[160, 90, 20, 14, 14, 12, 11, 10, 5, 10, 11, 12, 14, 14, 20, 90, 160],
+];
+
+const MEDIUM_RISK_TABLES: [&[u32]; 9] = [
+    &[130, 30, 13, 7, 4, 7, 13, 30, 130],
+    &[180, 40, 17, 9, 5, 5, 9, 17, 40, 180],
+    &[220, 50, 20, 14, 6, 4, 6, 14, 20, 50, 220],
+    &[240, 60, 30, 18, 7, 5, 5, 7, 18, 30, 60, 240],
+    &[330, 110, 40, 20, 11, 6, 3, 6, 11, 20, 40, 110, 330],
+    &[430, 130, 60, 30, 13, 7, 4, 4, 7, 13, 30, 60, 130, 430],
+    &[580, 150, 70, 40, 19, 10, 5, 2, 5, 10, 19, 40, 70, 150, 580],
+    &[880, 180, 110, 50, 30, 13, 5, 3, 3, 5, 13, 30, 50, 110, 180, 880],
+    &[1100, 410, 100, 50, 30, 15, 10, 5, 3, 5, 10, 15, 30, 50, 100, 410, 1100],
+];
+
+const HIGH_RISK_TABLES: [&[u32]; 9] = [
+    &[290, 40, 15, 3, 2, 3, 15, 40, 290],
+    &[430, 70, 20, 6, 2, 2, 6, 20, 70, 430],
+    &[760, 100, 30, 9, 3, 2, 3, 9, 30, 100, 760],
+    &[1200, 140, 52, 14, 4, 2, 2, 4, 14, 52, 140, 1200],
+    &[1700, 240, 81, 20, 7, 2, 2, 2, 7, 20, 81, 240, 1700],
+    &[2600, 370, 110, 40, 10, 2, 2, 2, 2, 10, 40, 110, 370, 2600],
+    &[4200, 560, 180, 50, 19, 3, 2, 2, 2, 3, 19, 50, 180, 560, 4200],
+    &[6200, 830, 270, 80, 30, 5, 2, 2, 2, 2, 5, 30, 80, 270, 830, 6200],
+    &[10000, 1300, 260, 90, 40, 20, 2, 2, 2, 2, 2, 20, 40, 90, 260, 1300, 10000],
+];
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PlinkoRisk {
+    Low,
+    Medium,
+    High,
+}
+
+// The path reads top to bottom, `R` for a bounce to the right, and the slot counts the right bounces
+#[derive(Serialize, Deserialize)]
+pub struct PlinkoResult {
+    account_id: AccountId,
+    risk: PlinkoRisk,
+    rows: u8,
+    path: String,
+    slot: u8,
+    multiplier: Multiplier,
+    total_winning: Balance,
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn play_plinko(&mut self, risk: PlinkoRisk, rows: u8, bet: U128) -> PlinkoResult {
+        let paytable = plinko_paytable(risk, rows);
+        let max_multiplier = paytable.iter().max().cloned().unwrap();
+        self.assert_exposure(max_multiplier.apply(bet.0), bet.0);
+        assert!(bet.0 <= self.plinko_max_bet(risk, rows), "ERR_BET_ABOVE_MAX");
+        let account_id = env::predecessor_account_id();
+        let mut account = self.users.get(&account_id).expect("Account Id does not exist");
+
+        let bounces = self.next_random_number();
+        let path: String = (0..rows).map(|row| if bounces >> row & 1 == 1 { 'R' } else { 'L' }).collect();
+        let slot = path.matches('R').count() as u8;
+        let multiplier = paytable[slot as usize];
+        let total_winning = multiplier.apply(bet.0);
+
+        self.settle(&mut account, "plinko", bet.0, total_winning);
+
+        PlinkoResult {
+            account_id,
+            risk,
+            rows,
+            path,
+            slot,
+            multiplier,
+            total_winning,
+        }
+    }
+
+    pub fn get_plinko_paytable(&self, risk: PlinkoRisk, rows: u8) -> Vec<Multiplier> {
+        plinko_paytable(risk, rows)
+    }
+}
+
+pub(crate) fn plinko_paytable(risk: PlinkoRisk, rows: u8) -> Vec<Multiplier> {
+    assert!((MIN_ROWS..=MAX_ROWS).contains(&rows), "ERR_ROWS_NOT_VALID");
+    let tables = match risk {
+        PlinkoRisk::Low => &LOW_RISK_TABLES,
+        PlinkoRisk::Medium => &MEDIUM_RISK_TABLES,
+        PlinkoRisk::High => &HIGH_RISK_TABLES,
+    };
+    tables[(rows - MIN_ROWS) as usize]
+        .iter()
+        .map(|tenths| Multiplier::from_bps(*tenths as u128 * 1_000))
+        .collect()
+}
+
+// The slot counts right bounces, so it follows a binomial distribution over 2^rows paths
+pub(crate) fn plinko_weights(rows: u8) -> Vec<u128> {
+    (0..=rows as u128).map(|slot| combinations(rows as u128, slot)).collect()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payout::{combinations, expected_return_bps};
+    use crate::tests::get_context;
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn every_paytable_keeps_a_house_edge() {
+        for risk in [PlinkoRisk::Low, PlinkoRisk::Medium, PlinkoRisk::High].iter() {
+            for rows in MIN_ROWS..=MAX_ROWS {
+                let paytable = plinko_paytable(*risk, rows);
+                assert_eq!(rows as usize + 1, paytable.len());
+                let weights: Vec<u128> = (0..=rows as u128).map(|slot| combinations(rows as u128, slot)).collect();
+                let rtp = expected_return_bps(&paytable, &weights, 1 << rows);
+                assert!(rtp > 9_850 && rtp < 9_950, "{} rows returns {}", rows, rtp);
+            }
+        }
+        let paytable = plinko_paytable(PlinkoRisk::High, 16);
+        assert_eq!(Multiplier::whole(1000), paytable[16]);
+    }
+
+    #[test]
+    fn play_plinko_follows_the_path() {
+        let mut context = get_context(vec![], false);
+        context.attached_deposit = 1000;
+        testing_env!(context);
+        let mut contract = Contract { house: 1_000_000, ..Default::default() };
+        contract.deposit(None);
+
+        let result = contract.play_plinko(PlinkoRisk::Medium, 12, U128(100));
+        assert_eq!(12, result.path.len());
+        assert_eq!(result.path.matches('R').count() as u8, result.slot);
+        assert_eq!(plinko_paytable(PlinkoRisk::Medium, 12)[result.slot as usize], result.multiplier);
+        assert_eq!(900 + result.multiplier.apply(100), contract.users.get(&"carol_near".to_string()).unwrap().deposit);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_EXPOSURE_TOO_HIGH")]
+    fn play_plinko_respects_exposure_limit() {
+        let mut context = get_context(vec![], false);
+        context.attached_deposit = 1000;
+        testing_env!(context);
+        let mut contract = Contract { house: 10_000, ..Default::default() };
+        contract.deposit(None);
+        contract.play_plinko(PlinkoRisk::High, 16, U128(100));
+    }
+}
@@ -0,0 +1,383 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{U128, U64, ValidAccountId};
+use near_sdk::{env, near_bindgen, AccountId, Balance, Promise};
+use serde::{Serialize, Deserialize};
+
+use crate::events::emit_event;
+use crate::payout::mul_div;
+use crate::*;
+
+pub const DEFAULT_UNLOCK_DELAY: u64 = 3 * 86_400 * 1_000_000_000;
+// A pool opens with at least 1 NEAR, which keeps a share worth too little for a first provider
+// to inflate its price at the expense of the next one, and starts over once the house falls below
+const MIN_POOL_HOUSE: Balance = 1_000_000_000_000_000_000_000_000;
+
+// Who may spend the house:
+// - settled bets, jackpot contributions, referral commission and rakeback, which every provider
+//   shares pro rata;
+// - owner promotions (bonus grants, tournament seeds, voucher winnings), which go through
+//   `spend_house` and are paid for with the owner's own shares;
+// - providers redeeming shares, up to what open rounds have not reserved (`house_liability`).
+
+// Shares queued for withdrawal keep sharing in the house P&L until they are redeemed
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+pub struct PendingWithdrawal {
+    shares: Balance,
+    unlocks_at: U64,
+}
+
+// Positions from an earlier epoch were wiped out when the house ran dry
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Default)]
+pub struct LiquidityPosition {
+    epoch: u64,
+    shares: Balance,
+    pending: Option<PendingWithdrawal>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LiquidityStatus {
+    account_id: AccountId,
+    shares: Balance,
+    value: Balance,
+    pending: Option<PendingWithdrawal>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PoolInfo {
+    house: Balance,
+    reserved: Balance,
+    total_shares: Balance,
+    unlock_delay: U64,
+}
+
+#[derive(Serialize)]
+struct LiquidityLog {
+    account_id: AccountId,
+    amount: Balance,
+    shares: Balance,
+}
+
+#[near_bindgen]
+impl Contract {
+    // Shares are minted at the current price of the house, so earlier providers keep their P&L
+    #[payable]
+    pub fn add_liquidity(&mut self) -> LiquidityStatus {
+        let account_id = env::predecessor_account_id();
+        let amount = env::attached_deposit();
+        assert!(amount > 0, "ERR_ZERO_DEPOSIT");
+        if self.pool_shares > 0 && self.house < MIN_POOL_HOUSE {
+            // The outstanding shares are worth next to nothing, so the pool starts over
+            self.pool_epoch += 1;
+            self.pool_shares = 0;
+        }
+        if self.pool_shares == 0 {
+            assert!(add_balance(self.house, amount) >= MIN_POOL_HOUSE, "ERR_DEPOSIT_TOO_SMALL");
+        }
+        if self.pool_shares == 0 && self.house > 0 {
+            // A bankroll built up before the pool opened belongs to the owner
+            let owner_id = self.owner_id.clone();
+            let house = self.house;
+            self.mint_pool_shares(&owner_id, house);
+        }
+
+        let shares = if self.pool_shares == 0 { amount } else { mul_div(amount, self.pool_shares, self.house) };
+        assert!(shares > 0, "ERR_DEPOSIT_TOO_SMALL");
+        self.house = add_balance(self.house, amount);
+        self.mint_pool_shares(&account_id, shares);
+        emit_event("liquidity_added", vec![LiquidityLog { account_id: account_id.clone(), amount, shares }]);
+        self.liquidity_status(account_id)
+    }
+
+    // Replaces any earlier request and restarts the unlock delay
+    pub fn request_liquidity_withdrawal(&mut self, shares: U128) -> LiquidityStatus {
+        let account_id = env::predecessor_account_id();
+        let mut position = self.pool_position(&account_id).expect("ERR_NO_LIQUIDITY");
+        assert!(shares.0 > 0 && shares.0 <= position.shares, "ERR_SHARES_NOT_VALID");
+        let unlocks_at = env::block_timestamp() + self.pool_unlock_delay;
+        position.pending = Some(PendingWithdrawal { shares: shares.0, unlocks_at: U64(unlocks_at) });
+        self.pool_positions.insert(&account_id, &position);
+        self.liquidity_status(account_id)
+    }
+
+    pub fn cancel_liquidity_withdrawal(&mut self) -> LiquidityStatus {
+        let account_id = env::predecessor_account_id();
+        let mut position = self.pool_position(&account_id).expect("ERR_NO_LIQUIDITY");
+        assert!(position.pending.take().is_some(), "ERR_NO_PENDING_WITHDRAWAL");
+        self.pool_positions.insert(&account_id, &position);
+        self.liquidity_status(account_id)
+    }
+
+    // Pending shares are redeemed at the share price when the withdrawal completes, as long as
+    // the house keeps enough to pay the open rounds at their best case for the players
+    pub fn withdraw_liquidity(&mut self) -> Balance {
+        let account_id = env::predecessor_account_id();
+        let mut position = self.pool_position(&account_id).expect("ERR_NO_LIQUIDITY");
+        let pending = position.pending.take().expect("ERR_NO_PENDING_WITHDRAWAL");
+        assert!(env::block_timestamp() >= pending.unlocks_at.0, "ERR_WITHDRAWAL_LOCKED");
+
+        let amount = self.share_value(pending.shares);
+        assert!(amount <= self.free_house(), "ERR_LIQUIDITY_RESERVED");
+        self.house -= amount;
+        self.pool_shares -= pending.shares;
+        position.shares -= pending.shares;
+        if position.shares == 0 {
+            self.pool_positions.remove(&account_id);
+        } else {
+            self.pool_positions.insert(&account_id, &position);
+        }
+        emit_event("liquidity_withdrawn", vec![LiquidityLog { account_id: account_id.clone(), amount, shares: pending.shares }]);
+        Promise::new(account_id).transfer(amount);
+        amount
+    }
+
+    pub fn get_liquidity(&self, account_id: ValidAccountId) -> LiquidityStatus {
+        self.liquidity_status(account_id.into())
+    }
+
+    pub fn get_pool_info(&self) -> PoolInfo {
+        PoolInfo {
+            house: self.house,
+            reserved: self.house_liability,
+            total_shares: self.pool_shares,
+            unlock_delay: U64(self.pool_unlock_delay),
+        }
+    }
+
+    pub fn set_pool_unlock_delay(&mut self, unlock_delay: U64) {
+        self.assert_owner();
+        self.pool_unlock_delay = unlock_delay.0;
+    }
+}
+
+impl Contract {
+    // Owner promotions are paid out of the house by burning owner shares worth the amount, rounded
+    // up, so the other providers keep their value. Before the pool opens the house is all the owner's.
+    pub(crate) fn spend_house(&mut self, amount: Balance) {
+        assert!(amount <= self.free_house(), "ERR_HOUSE_BALANCE_NOT_SUFFICIENT");
+        if self.pool_shares > 0 {
+            let owner_id = self.owner_id.clone();
+            let mut shares = mul_div(amount, self.pool_shares, self.house);
+            if self.share_value(shares) < amount {
+                shares += 1;
+            }
+            let mut position = self.pool_position(&owner_id).unwrap_or_default();
+            let locked = position.pending.as_ref().map_or(0, |pending| pending.shares);
+            assert!(shares + locked <= position.shares, "ERR_OWNER_LIQUIDITY_NOT_SUFFICIENT");
+            position.shares -= shares;
+            self.pool_shares -= shares;
+            if position.shares == 0 {
+                self.pool_positions.remove(&owner_id);
+            } else {
+                self.pool_positions.insert(&owner_id, &position);
+            }
+        }
+        self.house -= amount;
+    }
+
+    // Open rounds reserve what they can still pay out, and release it when they settle
+    pub(crate) fn reserve_house(&mut self, amount: Balance) {
+        self.house_liability = add_balance(self.house_liability, amount);
+    }
+
+    pub(crate) fn release_house(&mut self, amount: Balance) {
+        self.house_liability -= amount;
+    }
+
+    // The part of the house that open rounds have not reserved, which new bets are sized against
+    pub(crate) fn free_house(&self) -> Balance {
+        self.house.saturating_sub(self.house_liability)
+    }
+
+    fn pool_position(&self, account_id: &AccountId) -> Option<LiquidityPosition> {
+        self.pool_positions.get(account_id).filter(|position| position.epoch == self.pool_epoch)
+    }
+
+    fn liquidity_status(&self, account_id: AccountId) -> LiquidityStatus {
+        let position = self.pool_position(&account_id).unwrap_or_default();
+        LiquidityStatus {
+            account_id,
+            shares: position.shares,
+            value: self.share_value(position.shares),
+            pending: position.pending,
+        }
+    }
+
+    fn mint_pool_shares(&mut self, account_id: &AccountId, shares: Balance) {
+        let mut position = self.pool_position(account_id).unwrap_or(LiquidityPosition { epoch: self.pool_epoch, ..Default::default() });
+        position.shares = add_balance(position.shares, shares);
+        self.pool_positions.insert(account_id, &position);
+        self.pool_shares = add_balance(self.pool_shares, shares);
+    }
+
+    fn share_value(&self, shares: Balance) -> Balance {
+        if self.pool_shares == 0 {
+            return 0;
+        }
+        mul_div(shares, self.house, self.pool_shares)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{bets, get_context, set_context};
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+    use std::convert::TryInto;
+
+    const ONE_NEAR: Balance = MIN_POOL_HOUSE;
+
+    #[test]
+    fn providers_share_the_house_result() {
+        testing_env!(get_context(vec![], false));
+        let mut contract = Contract::default();
+        set_context("dave_near", 0, 0, 3000 * ONE_NEAR);
+        contract.add_liquidity();
+        set_context("erin_near", 0, 0, 1000 * ONE_NEAR);
+        contract.add_liquidity();
+        assert_eq!(4000 * ONE_NEAR, contract.get_pool_info().total_shares);
+
+        // Under a zero seed 5-2-2 loses a bet on big, and 1% of it goes to the jackpot
+        set_context("carol_near", 0, 0, 1000 * ONE_NEAR);
+        contract.deposit(None);
+        contract.play_sicbo(bets(&[("big", 400 * ONE_NEAR)]), None);
+        assert_eq!(4396 * ONE_NEAR, contract.house);
+        assert_eq!(3297 * ONE_NEAR, contract.get_liquidity("dave_near".try_into().unwrap()).value);
+
+        // A later provider buys in at the new price
+        set_context("frank_near", 0, 0, 1099 * ONE_NEAR);
+        assert_eq!(1000 * ONE_NEAR, contract.add_liquidity().shares);
+
+        set_context("erin_near", 0, 0, 0);
+        contract.request_liquidity_withdrawal(U128(1000 * ONE_NEAR));
+        set_context("erin_near", 0, DEFAULT_UNLOCK_DELAY, 0);
+        assert_eq!(1099 * ONE_NEAR, contract.withdraw_liquidity());
+        assert_eq!(4396 * ONE_NEAR, contract.house);
+        assert_eq!(4000 * ONE_NEAR, contract.pool_shares);
+        assert!(contract.pool_positions.get(&"erin_near".to_string()).is_none());
+    }
+
+    #[test]
+    fn existing_bankroll_goes_to_the_owner() {
+        testing_env!(get_context(vec![], false));
+        let mut contract = Contract { house: 500 * ONE_NEAR, ..Default::default() };
+        set_context("dave_near", 0, 0, 1000 * ONE_NEAR);
+        contract.add_liquidity();
+        assert_eq!(500 * ONE_NEAR, contract.get_liquidity("alice_near".try_into().unwrap()).value);
+        assert_eq!(1000 * ONE_NEAR, contract.get_liquidity("dave_near".try_into().unwrap()).value);
+    }
+
+    #[test]
+    fn open_rounds_are_reserved_until_they_settle() {
+        testing_env!(get_context(vec![], false));
+        let mut contract = Contract::default();
+        set_context("dave_near", 0, 0, 1000 * ONE_NEAR);
+        contract.add_liquidity();
+        set_context("carol_near", 0, 0, 1000 * ONE_NEAR);
+        contract.deposit(None);
+        contract.mines_start(U128(100 * ONE_NEAR), 1);
+        assert_eq!(100 * ONE_NEAR, contract.get_pool_info().reserved);
+
+        // Under a zero seed the first reveal with a single mine is safe
+        contract.mines_reveal(0);
+        assert_eq!(contract.get_mines_multiplier(1, 1).apply(100 * ONE_NEAR), contract.get_pool_info().reserved);
+        contract.mines_cashout();
+        assert_eq!(0, contract.get_pool_info().reserved);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_LIQUIDITY_RESERVED")]
+    fn withdrawal_leaves_open_rounds_covered() {
+        testing_env!(get_context(vec![], false));
+        let mut contract = Contract::default();
+        set_context("dave_near", 0, 0, 1000 * ONE_NEAR);
+        contract.add_liquidity();
+        contract.request_liquidity_withdrawal(U128(1000 * ONE_NEAR));
+        set_context("carol_near", 0, 0, 1000 * ONE_NEAR);
+        contract.deposit(None);
+        contract.hilo_start(U128(100 * ONE_NEAR));
+
+        set_context("dave_near", 0, DEFAULT_UNLOCK_DELAY, 0);
+        contract.withdraw_liquidity();
+    }
+
+    #[test]
+    fn owner_promotions_burn_owner_shares() {
+        testing_env!(get_context(vec![], false));
+        let mut contract = Contract { house: 500 * ONE_NEAR, ..Default::default() };
+        set_context("dave_near", 0, 0, 1000 * ONE_NEAR);
+        contract.add_liquidity();
+        set_context("carol_near", 0, 0, 1000 * ONE_NEAR);
+        contract.deposit(None);
+
+        set_context("alice_near", 0, 0, 0);
+        contract.grant_bonus("carol_near".try_into().unwrap(), U128(200 * ONE_NEAR));
+        assert_eq!(300 * ONE_NEAR, contract.get_liquidity("alice_near".try_into().unwrap()).shares);
+        assert_eq!(1000 * ONE_NEAR, contract.get_liquidity("dave_near".try_into().unwrap()).value);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_OWNER_LIQUIDITY_NOT_SUFFICIENT")]
+    fn owner_promotions_cannot_spend_provider_funds() {
+        testing_env!(get_context(vec![], false));
+        let mut contract = Contract { house: 500 * ONE_NEAR, ..Default::default() };
+        set_context("dave_near", 0, 0, 1000 * ONE_NEAR);
+        contract.add_liquidity();
+        set_context("carol_near", 0, 0, 1000 * ONE_NEAR);
+        contract.deposit(None);
+
+        set_context("alice_near", 0, 0, 0);
+        contract.grant_bonus("carol_near".try_into().unwrap(), U128(500 * ONE_NEAR + 1));
+    }
+
+    #[test]
+    fn drained_pool_starts_over() {
+        testing_env!(get_context(vec![], false));
+        let mut contract = Contract::default();
+        set_context("dave_near", 0, 0, 1000 * ONE_NEAR);
+        contract.add_liquidity();
+        contract.house = 0;
+
+        set_context("erin_near", 0, 0, 500 * ONE_NEAR);
+        assert_eq!(500 * ONE_NEAR, contract.add_liquidity().shares);
+        assert_eq!(500 * ONE_NEAR, contract.get_pool_info().total_shares);
+        assert_eq!(0, contract.get_liquidity("dave_near".try_into().unwrap()).shares);
+    }
+
+    #[test]
+    fn nearly_drained_pool_starts_over_without_overflow() {
+        testing_env!(get_context(vec![], false));
+        let mut contract = Contract::default();
+        set_context("dave_near", 0, 0, u128::MAX / 2);
+        contract.add_liquidity();
+        contract.house = 1;
+
+        // Priced at the old shares this deposit would mint more than fits in a u128
+        set_context("erin_near", 0, 0, 3 * ONE_NEAR);
+        assert_eq!(3 * ONE_NEAR, contract.add_liquidity().shares);
+        assert_eq!(1, contract.get_liquidity("alice_near".try_into().unwrap()).shares);
+        assert_eq!(0, contract.get_liquidity("dave_near".try_into().unwrap()).shares);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DEPOSIT_TOO_SMALL")]
+    fn first_deposit_needs_the_minimum() {
+        testing_env!(get_context(vec![], false));
+        let mut contract = Contract::default();
+        set_context("dave_near", 0, 0, ONE_NEAR - 1);
+        contract.add_liquidity();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_WITHDRAWAL_LOCKED")]
+    fn withdrawal_waits_for_the_unlock_delay() {
+        testing_env!(get_context(vec![], false));
+        let mut contract = Contract::default();
+        set_context("dave_near", 0, 0, 1000 * ONE_NEAR);
+        contract.add_liquidity();
+        contract.request_liquidity_withdrawal(U128(500 * ONE_NEAR));
+        set_context("dave_near", 0, DEFAULT_UNLOCK_DELAY - 1, 0);
+        contract.withdraw_liquidity();
+    }
+}
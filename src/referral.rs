@@ -0,0 +1,221 @@
+use near_sdk::json_types::ValidAccountId;
+use near_sdk::{env, near_bindgen, AccountId, Balance};
+use serde::{Serialize, Deserialize};
+
+use crate::keno::keno_min_edge_bps;
+use crate::payout::BASIS_POINTS;
+use crate::*;
+
+pub const DEFAULT_REFERRAL_COMMISSION_BPS: u32 = 1_000;
+
+// The lowest house edge of any bet on the fixed paytables. Sic bo's best bets are sum_4 and
+// sum_5 at 86.11%, roulette's is a straight at 35/37 and plinko's best table returns 99.16%.
+const SICBO_MIN_EDGE_BPS: u128 = 1_389;
+const ROULETTE_MIN_EDGE_BPS: u128 = 540;
+const PLINKO_MIN_EDGE_BPS: u128 = 80;
+// Basic strategy against a dealer without a hole card standing on soft 17
+const BLACKJACK_MIN_EDGE_BPS: u128 = 25;
+// Baccarat odds in parts per million, drawing from an infinite shoe. The player bet returns
+// 98.77%, the banker bet depends on the commission and ties push both.
+const BACCARAT_PLAYER_EDGE_BPS: u128 = 122;
+const BACCARAT_BANKER_WIN_PPM: u128 = 458_427;
+const BACCARAT_TIE_PPM: u128 = 95_425;
+
+#[derive(Serialize, Deserialize)]
+pub struct ReferralStats {
+    referrer: Option<AccountId>,
+    referred_accounts: u64,
+    total_earned: Balance,
+    claimable: Balance,
+}
+
+#[near_bindgen]
+impl Contract {
+    // Moves the accrued commission into the deposit, from where it can be withdrawn or played
+    pub fn claim_referral_rewards(&mut self) -> Balance {
+        let account_id = env::predecessor_account_id();
+        let mut account = self.users.get(&account_id).expect("Account Id does not exist");
+        assert!(account.referral_rewards > 0, "ERR_NO_REFERRAL_REWARDS");
+        account.deposit = add_balance(account.deposit, account.referral_rewards);
+        account.referral_rewards = 0;
+        self.users.insert(&account_id, &account);
+        account.deposit
+    }
+
+    pub fn get_referral_stats(&self, account_id: ValidAccountId) -> ReferralStats {
+        let account = self.get_account(account_id);
+        ReferralStats {
+            referrer: account.referrer,
+            referred_accounts: account.referred_accounts,
+            total_earned: account.referral_earned,
+            claimable: account.referral_rewards,
+        }
+    }
+
+    pub fn get_referral_commission(&self) -> u32 {
+        self.referral_commission_bps
+    }
+
+    pub fn set_referral_commission(&mut self, commission_bps: u32) {
+        self.assert_owner();
+        assert!(commission_bps as u128 <= BASIS_POINTS, "ERR_COMMISSION_NOT_VALID");
+        self.referral_commission_bps = commission_bps;
+    }
+}
+
+impl Contract {
+    pub(crate) fn register_referrer(&mut self, account_id: &str, referrer_id: AccountId) -> AccountId {
+        assert_ne!(account_id, referrer_id, "ERR_SELF_REFERRAL");
+        let mut referrer = self.users.get(&referrer_id).expect("ERR_REFERRER_NOT_FOUND");
+        referrer.referred_accounts += 1;
+        self.users.insert(&referrer_id, &referrer);
+        referrer_id
+    }
+
+    // The referrer earns a share of the house edge on every settled stake, whatever the result,
+    // so the commission never exceeds what the house expects to win from the bet
+    pub(crate) fn accrue_referral(&mut self, account: &Account, game: &str, stake: Balance) {
+        let referrer_id = match &account.referrer {
+            Some(referrer_id) => referrer_id,
+            None => return,
+        };
+        let edge = stake * self.min_house_edge_bps(game) / BASIS_POINTS;
+        let commission = edge * self.referral_commission_bps as u128 / BASIS_POINTS;
+        if commission == 0 {
+            return;
+        }
+
+        self.house = self.house.checked_sub(commission).expect("ERR_HOUSE_BALANCE_NOT_SUFFICIENT");
+        let mut referrer = self.users.get(referrer_id).expect("ERR_REFERRER_NOT_FOUND");
+        referrer.referral_rewards = add_balance(referrer.referral_rewards, commission);
+        referrer.referral_earned = add_balance(referrer.referral_earned, commission);
+        self.users.insert(referrer_id, &referrer);
+    }
+
+    pub(crate) fn min_house_edge_bps(&self, game: &str) -> u128 {
+        match game {
+            "dice" => BASIS_POINTS - DICE_RETURN.apply(BASIS_POINTS),
+            "sicbo" | "sicbo_table" => SICBO_MIN_EDGE_BPS,
+            "roulette" | "roulette_table" => ROULETTE_MIN_EDGE_BPS,
+            "plinko" => PLINKO_MIN_EDGE_BPS,
+            "blackjack" => BLACKJACK_MIN_EDGE_BPS,
+            "keno" => keno_min_edge_bps(&self.keno_paytables),
+            "baccarat" => {
+                let commission = self.baccarat_commission_bps as u128;
+                let banker_return = ((2 * BASIS_POINTS - commission) * BACCARAT_BANKER_WIN_PPM
+                    + BASIS_POINTS * BACCARAT_TIE_PPM)
+                    .div_ceil(1_000_000);
+                BACCARAT_PLAYER_EDGE_BPS.min(BASIS_POINTS.saturating_sub(banker_return))
+            }
+            "hilo" => self.hilo_config.house_edge_bps as u128,
+            "mines" => self.mines_house_edge_bps as u128,
+            "crash" => self.crash_config.house_edge_bps as u128,
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payout::{expected_return_bps, Multiplier};
+    use crate::plinko::{plinko_paytable, plinko_weights, PlinkoRisk};
+    use crate::tests::{get_context, set_context};
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+    use std::convert::TryInto;
+
+    fn setup() -> Contract {
+        testing_env!(get_context(vec![], false));
+        let mut contract = Contract { house: 1_000_000, ..Default::default() };
+        set_context("dave_near", 0, 0, 1000);
+        contract.deposit(None);
+        set_context("carol_near", 0, 0, 1000);
+        contract.deposit(Some("dave_near".try_into().unwrap()));
+        contract
+    }
+
+    #[test]
+    fn referrer_earns_a_share_of_the_edge() {
+        let mut contract = setup();
+        // A later deposit cannot change the referrer
+        contract.deposit(Some("alice_near".try_into().unwrap()));
+
+        // A zero seed rolls 30.36, so the whole stake is lost, but only the 1.5% edge is shared
+        let result = contract.play_dice(DiceMode::RollOver, 5000, U128(1000), None);
+        assert_eq!(0, result.total_winning);
+        let stats = contract.get_referral_stats("dave_near".try_into().unwrap());
+        assert_eq!(1, stats.referred_accounts);
+        assert_eq!(1, stats.claimable);
+        assert_eq!(1, stats.total_earned);
+        assert_eq!(Some("dave_near".to_string()), contract.get_referral_stats("carol_near".try_into().unwrap()).referrer);
+
+        set_context("dave_near", 0, 0, 0);
+        assert_eq!(1001, contract.claim_referral_rewards());
+        assert_eq!(0, contract.get_referral_stats("dave_near".try_into().unwrap()).claimable);
+    }
+
+    #[test]
+    fn commission_stays_below_the_edge_on_even_odds() {
+        let mut contract = setup();
+        set_context("carol_near", 0, 0, 200_000);
+        contract.deposit(None);
+        let stake = 100_000;
+        let multiplier = dice_multiplier(DiceMode::RollOver, 4999);
+        let expected_return = multiplier.apply(stake) * dice_winning_outcomes(DiceMode::RollOver, 4999) as u128 / DICE_OUTCOMES as u128;
+        let expected_edge = stake - expected_return;
+
+        // Winning and losing bets earn the same 10% of the 1,500 the house expects from each
+        let mut account = contract.users.get(&"carol_near".to_string()).unwrap();
+        contract.settle(&mut account, "dice", stake, multiplier.apply(stake));
+        let mut account = contract.users.get(&"carol_near".to_string()).unwrap();
+        contract.settle(&mut account, "dice", stake, 0);
+        let rewards = contract.users.get(&"dave_near".to_string()).unwrap().referral_rewards;
+        assert_eq!(300, rewards);
+        assert!(rewards < 2 * expected_edge);
+    }
+
+    #[test]
+    fn commission_is_paid_by_the_house() {
+        let mut contract = setup();
+        set_context("carol_near", 0, 0, 200_000);
+        contract.deposit(None);
+        let mut account = contract.users.get(&"carol_near".to_string()).unwrap();
+        contract.settle(&mut account, "roulette", 50_000, 0);
+        assert_eq!(1_000_000 + 50_000 - 270, contract.house);
+        assert_eq!(270, contract.users.get(&"dave_near".to_string()).unwrap().referral_rewards);
+    }
+
+    #[test]
+    fn minimum_edges_cover_every_fixed_paytable() {
+        let sicbo_bets = ["small", "big", "double_1", "triple_any", "triple_1", "single_1", "comb_1_2"]
+            .iter()
+            .map(|bet| bet.to_string())
+            .chain((4..=17).map(|sum| format!("sum_{}", sum)));
+        for bet in sicbo_bets {
+            let paytable: Vec<Multiplier> = (0..SICBO_OUTCOMES).map(|outcome| sicbo_multiplier(&bet, outcome)).collect();
+            let rtp = expected_return_bps(&paytable, &[1; SICBO_OUTCOMES as usize], SICBO_OUTCOMES as u128);
+            assert!(rtp + SICBO_MIN_EDGE_BPS <= BASIS_POINTS, "{}", bet);
+        }
+        for bet in ["7", "0|1", "red", "1st_12", "low"].iter() {
+            let paytable: Vec<Multiplier> = (0..ROULETTE_OUTCOMES).map(|outcome| roulette_multiplier(bet, outcome)).collect();
+            let rtp = expected_return_bps(&paytable, &[1; ROULETTE_OUTCOMES as usize], ROULETTE_OUTCOMES as u128);
+            assert!(rtp + ROULETTE_MIN_EDGE_BPS <= BASIS_POINTS, "{}", bet);
+        }
+        for risk in [PlinkoRisk::Low, PlinkoRisk::Medium, PlinkoRisk::High].iter() {
+            for rows in 8..=16 {
+                let rtp = expected_return_bps(&plinko_paytable(*risk, rows), &plinko_weights(rows), 1 << rows);
+                assert!(rtp + PLINKO_MIN_EDGE_BPS <= BASIS_POINTS);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_SELF_REFERRAL")]
+    fn self_referral_is_rejected() {
+        testing_env!(get_context(vec![], false));
+        let mut contract = Contract::default();
+        contract.deposit(Some("carol_near".try_into().unwrap()));
+    }
+}
@@ -0,0 +1,322 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{U128, U64, ValidAccountId};
+use near_sdk::{env, near_bindgen, AccountId, Balance, BlockHeight};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+use crate::crash::participant_key;
+use crate::events::emit_event;
+use crate::payout::Multiplier;
+use crate::*;
+
+pub const DEFAULT_TABLE_BLOCKS: BlockHeight = 30;
+
+// Bets accumulate until `betting_closes`, then a single spin settles every participant.
+// Participants are kept in `table_participants` by the order of their first bet.
+// `outcome_winnings` holds what the round would pay out in total for each of the 37 pockets,
+// so the exposure cap covers every participant.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+pub struct RouletteRound {
+    id: u64,
+    betting_closes: BlockHeight,
+    participants: u64,
+    roulette_value: Option<u8>,
+    settled_participants: u64,
+    total_stake: Balance,
+    total_winning: Balance,
+    #[serde(skip)]
+    outcome_winnings: Vec<Balance>,
+}
+
+// `reserved` is what the bets pay on their best outcome, held back from the house until the spin
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+pub struct RouletteTableBet {
+    bets: HashMap<Bet, Balance>,
+    bonus_stake: Balance,
+    placed_at: U64,
+    reserved: Balance,
+    total_winning: Option<Balance>,
+}
+
+#[derive(Serialize)]
+struct TableSpin {
+    round_id: u64,
+    roulette_value: u8,
+}
+
+#[derive(Serialize)]
+pub(crate) struct TableSettlement {
+    pub(crate) round_id: u64,
+    pub(crate) account_id: AccountId,
+    pub(crate) total_stake: Balance,
+    pub(crate) total_winning: Balance,
+}
+
+#[near_bindgen]
+impl Contract {
+    // Adds to the caller's bets in the open round, opening a new one if the last round has closed
+    pub fn place_table_bet(&mut self, bets: HashMap<Bet, U128>) -> RouletteRound {
+        let account_id = env::predecessor_account_id();
+        let mut round = self.open_table_round();
+        let bet_key = table_bet_key(round.id, &account_id);
+        let mut table_bet = match self.table_bets.get(&bet_key) {
+            Some(table_bet) => table_bet,
+            None => {
+                self.table_participants.insert(&participant_key(round.id, round.participants), &account_id);
+                round.participants += 1;
+                RouletteTableBet {
+                    bets: HashMap::new(),
+                    bonus_stake: 0,
+                    placed_at: U64(env::block_timestamp()),
+                    reserved: 0,
+                    total_winning: None,
+                }
+            }
+        };
+
+        let mut total: u128 = 0;
+        for (bet, balance) in bets {
+            assert!(balance.0 > 0, "ERR_ZERO_BET");
+            // Validates the bet before any stake is taken
+            let max_bet = self.roulette_max_bet(&bet);
+            for outcome in 0..ROULETTE_OUTCOMES {
+                let multiplier = Multiplier::whole(check_point_roulette(&bet, outcome as u8));
+                let winnings = &mut round.outcome_winnings[outcome as usize];
+                *winnings = add_balance(*winnings, multiplier.apply(balance.0));
+            }
+            total = add_balance(total, balance.0);
+            let amount = table_bet.bets.entry(bet).or_insert(0);
+            *amount = add_balance(*amount, balance.0);
+            assert!(*amount <= max_bet, "ERR_BET_ABOVE_MAX");
+        }
+        let total_stake = add_balance(round.total_stake, total);
+        let best_case = round.outcome_winnings.iter().max().cloned().unwrap_or(0);
+        self.assert_exposure(best_case, total_stake);
+
+        let mut account = self.users.get(&account_id).expect("Account Id does not exist");
+        let bonus_stake = self.collect_stake(&mut account, total);
+        self.users.insert(&account_id, &account);
+        table_bet.bonus_stake = add_balance(table_bet.bonus_stake, bonus_stake);
+        let reserved = (0..ROULETTE_OUTCOMES)
+            .map(|outcome| table_winning(&table_bet.bets, |bet| check_point_roulette(bet, outcome as u8)))
+            .max()
+            .unwrap_or(0);
+        self.reserve_house(reserved - table_bet.reserved);
+        table_bet.reserved = reserved;
+
+        self.table_bets.insert(&bet_key, &table_bet);
+        round.total_stake = total_stake;
+        self.table_rounds.insert(&round.id, &round);
+        round
+    }
+
+    // Spins the wheel on the first call and settles up to `limit` participants per call
+    pub fn spin_table(&mut self, round_id: u64, limit: u64) -> RouletteRound {
+        let mut round = self.table_rounds.get(&round_id).expect("ERR_ROUND_NOT_FOUND");
+        assert!(env::block_index() > round.betting_closes, "ERR_ROUND_NOT_CLOSED");
+        assert!(round.settled_participants < round.participants, "ERR_ROUND_ALREADY_SETTLED");
+
+        let roulette_value = match round.roulette_value {
+            Some(roulette_value) => roulette_value,
+            None => {
+                let roulette_value = self.roulette_spin();
+                round.roulette_value = Some(roulette_value);
+                emit_event("roulette_table_spin", vec![TableSpin { round_id, roulette_value }]);
+                roulette_value
+            }
+        };
+
+        let end = round.settled_participants.saturating_add(limit).min(round.participants);
+        let mut settlements = Vec::new();
+        for index in round.settled_participants..end {
+            let account_id = self.table_participants.get(&participant_key(round_id, index)).unwrap();
+            let bet_key = table_bet_key(round_id, &account_id);
+            let mut table_bet = self.table_bets.get(&bet_key).unwrap();
+
+            let total_stake = table_bet.bets.values().fold(0, |total, amount| add_balance(total, *amount));
+            let total_winning = table_winning(&table_bet.bets, |bet| check_point_roulette(bet, roulette_value));
+
+            let mut account = self.users.get(&account_id).expect("Account Id does not exist");
+            self.release_house(table_bet.reserved);
+            self.pay_winning(&mut account, "roulette_table", total_stake, table_bet.bonus_stake, total_winning, table_bet.placed_at.0, false);
+            self.users.insert(&account_id, &account);
+            self.check_roulette_achievements(&account_id, table_bet.bets.keys(), roulette_value);
+
+            table_bet.total_winning = Some(total_winning);
+            self.table_bets.insert(&bet_key, &table_bet);
+            round.total_winning = add_balance(round.total_winning, total_winning);
+            settlements.push(TableSettlement { round_id, account_id, total_stake, total_winning });
+        }
+        emit_event("roulette_table_settle", settlements);
+
+        round.settled_participants = end;
+        self.table_rounds.insert(&round_id, &round);
+        round
+    }
+
+    pub fn get_table_round(&self, round_id: u64) -> Option<RouletteRound> {
+        self.table_rounds.get(&round_id)
+    }
+
+    pub fn get_table_round_id(&self) -> u64 {
+        self.table_round_id
+    }
+
+    pub fn get_table_participants(&self, round_id: u64, from_index: u64, limit: u64) -> Vec<AccountId> {
+        let round = self.table_rounds.get(&round_id).expect("ERR_ROUND_NOT_FOUND");
+        (from_index..round.participants.min(from_index.saturating_add(limit)))
+            .map(|index| self.table_participants.get(&participant_key(round_id, index)).unwrap())
+            .collect()
+    }
+
+    pub fn get_table_bet(&self, round_id: u64, account_id: ValidAccountId) -> Option<RouletteTableBet> {
+        self.table_bets.get(&table_bet_key(round_id, account_id.as_ref()))
+    }
+
+    pub fn get_table_blocks(&self) -> BlockHeight {
+        self.table_blocks
+    }
+
+    pub fn set_table_blocks(&mut self, table_blocks: BlockHeight) {
+        self.assert_owner();
+        assert!(table_blocks > 0, "ERR_TABLE_BLOCKS_NOT_VALID");
+        self.table_blocks = table_blocks;
+    }
+}
+
+impl Contract {
+    fn open_table_round(&mut self) -> RouletteRound {
+        let block_index = env::block_index();
+        if let Some(round) = self.table_rounds.get(&self.table_round_id) {
+            if block_index <= round.betting_closes {
+                return round;
+            }
+        }
+
+        self.table_round_id += 1;
+        RouletteRound {
+            id: self.table_round_id,
+            betting_closes: block_index + self.table_blocks,
+            participants: 0,
+            roulette_value: None,
+            settled_participants: 0,
+            total_stake: 0,
+            total_winning: 0,
+            outcome_winnings: vec![0; ROULETTE_OUTCOMES as usize],
+        }
+    }
+}
+
+pub(crate) fn table_bet_key(round_id: u64, account_id: &str) -> String {
+    format!("{}:{}", round_id, account_id)
+}
+
+// What a participant's bets pay for one outcome, given the whole multiplier of each bet key
+pub(crate) fn table_winning<F: Fn(&str) -> u32>(bets: &HashMap<Bet, Balance>, multiplier: F) -> Balance {
+    bets.iter().fold(0, |total, (bet, amount)| add_balance(total, Multiplier::whole(multiplier(bet)).apply(*amount)))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{bets, get_context, set_context};
+    use near_sdk::test_utils::get_logs;
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn one_spin_settles_every_participant() {
+        testing_env!(get_context(vec![], false));
+        set_context("carol_near", 0, 0, 1000);
+        let mut contract = Contract { house: 100_000, ..Default::default() };
+        contract.deposit(None);
+        set_context("dave_near", 0, 0, 1000);
+        contract.deposit(None);
+
+        contract.place_table_bet(bets(&[("red", 100)]));
+        set_context("carol_near", 10, 0, 0);
+        contract.place_table_bet(bets(&[("black", 100)]));
+        let round = contract.place_table_bet(bets(&[("black", 50), ("17", 10)]));
+        assert_eq!(1, round.id);
+        assert_eq!(2, round.participants);
+        assert_eq!(vec!["dave_near".to_string()], contract.get_table_participants(1, 0, 1));
+        assert_eq!(260, round.total_stake);
+        assert_eq!(840, contract.users.get(&"carol_near".to_string()).unwrap().deposit);
+
+        set_context("dave_near", 31, 0, 0);
+        let round = contract.spin_table(1, 1);
+        assert_eq!(1, round.settled_participants);
+        let round = contract.spin_table(1, 10);
+        assert_eq!(2, round.settled_participants);
+
+        let roulette_value = round.roulette_value.unwrap();
+        let logs = get_logs();
+        assert_eq!(3, logs.len());
+        assert_eq!(
+            format!(
+                "EVENT_JSON:{{\"standard\":\"sicbo\",\"version\":\"1.0.0\",\"event\":\"roulette_table_spin\",\"data\":[{{\"round_id\":1,\"roulette_value\":{}}}]}}",
+                roulette_value
+            ),
+            logs[0]
+        );
+        assert!(logs[2].contains("\"event\":\"roulette_table_settle\",\"data\":[{\"round_id\":1,\"account_id\":\"carol_near\""));
+        let expected = [
+            ("dave_near", vec![("red", 100)]),
+            ("carol_near", vec![("black", 150), ("17", 10)]),
+        ];
+        for (account_id, placed) in expected.iter() {
+            let total_winning: Balance = placed
+                .iter()
+                .map(|(bet, amount)| Multiplier::whole(check_point_roulette(bet, roulette_value)).apply(*amount))
+                .sum();
+            let staked: Balance = placed.iter().map(|(_, amount)| amount).sum();
+            let table_bet = contract.table_bets.get(&table_bet_key(1, account_id)).unwrap();
+            assert_eq!(Some(total_winning), table_bet.total_winning);
+            assert_eq!(1000 - staked + total_winning, contract.users.get(&account_id.to_string()).unwrap().deposit);
+        }
+
+        // A bet after the window closes opens the next round
+        let round = contract.place_table_bet(bets(&[("odd", 10)]));
+        assert_eq!(2, round.id);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ROUND_NOT_CLOSED")]
+    fn spin_before_window_closes_fails() {
+        testing_env!(get_context(vec![], false));
+        set_context("carol_near", 0, 0, 1000);
+        let mut contract = Contract { house: 100_000, ..Default::default() };
+        contract.deposit(None);
+        contract.place_table_bet(bets(&[("red", 100)]));
+        set_context("carol_near", 30, 0, 0);
+        contract.spin_table(1, 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_EXPOSURE_TOO_HIGH")]
+    fn round_exposure_is_capped() {
+        // Each straight-up bet alone stays under the 1% cap, together they exceed it
+        testing_env!(get_context(vec![], false));
+        set_context("carol_near", 0, 0, 1000);
+        let mut contract = Contract { house: 100_000, max_exposure_bps: 100, ..Default::default() };
+        contract.deposit(None);
+        set_context("dave_near", 0, 0, 1000);
+        contract.deposit(None);
+
+        let round = contract.place_table_bet(bets(&[("7", 20)]));
+        assert_eq!(700, round.outcome_winnings[7]);
+        set_context("carol_near", 1, 0, 0);
+        contract.place_table_bet(bets(&[("7", 20)]));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_UNKNOWN_BET")]
+    fn unknown_table_bet_is_rejected() {
+        testing_env!(get_context(vec![], false));
+        set_context("carol_near", 0, 0, 1000);
+        let mut contract = Contract::default();
+        contract.deposit(None);
+        contract.place_table_bet(bets(&[("purple", 100)]));
+    }
+}
@@ -0,0 +1,250 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{U128, U64, ValidAccountId};
+use near_sdk::{env, near_bindgen, AccountId, Balance, BlockHeight};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+use crate::crash::participant_key;
+use crate::events::emit_event;
+use crate::payout::Multiplier;
+use crate::roulette_table::{table_bet_key, table_winning, TableSettlement};
+use crate::*;
+
+// Shares the betting window of the roulette table. `outcome_winnings` holds what the round
+// would pay out in total for each of the 216 rolls, so the exposure cap covers every participant.
+// Participants are kept in `sicbo_table_participants` by the order of their first bet.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+pub struct SicBoRound {
+    id: u64,
+    betting_closes: BlockHeight,
+    participants: u64,
+    dices: Option<Vec<u8>>,
+    settled_participants: u64,
+    total_stake: Balance,
+    total_winning: Balance,
+    #[serde(skip)]
+    outcome_winnings: Vec<Balance>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+pub struct SicBoTableBet {
+    bets: HashMap<Bet, Balance>,
+    bonus_stake: Balance,
+    placed_at: U64,
+    reserved: Balance,
+    total_winning: Option<Balance>,
+}
+
+#[derive(Serialize)]
+struct SicBoRoll {
+    round_id: u64,
+    dices: Vec<u8>,
+}
+
+#[near_bindgen]
+impl Contract {
+    // Rejects bets that would push the round's worst case for the house past the exposure limit
+    pub fn place_sicbo_table_bet(&mut self, bets: HashMap<Bet, U128>) -> SicBoRound {
+        let account_id = env::predecessor_account_id();
+        let mut round = self.open_sicbo_table_round();
+        let bet_key = table_bet_key(round.id, &account_id);
+        let mut table_bet = match self.sicbo_table_bets.get(&bet_key) {
+            Some(table_bet) => table_bet,
+            None => {
+                self.sicbo_table_participants.insert(&participant_key(round.id, round.participants), &account_id);
+                round.participants += 1;
+                SicBoTableBet {
+                    bets: HashMap::new(),
+                    bonus_stake: 0,
+                    placed_at: U64(env::block_timestamp()),
+                    reserved: 0,
+                    total_winning: None,
+                }
+            }
+        };
+
+        let mut total: u128 = 0;
+        for (bet, balance) in bets {
+            assert!(balance.0 > 0, "ERR_ZERO_BET");
+            for outcome in 0..SICBO_OUTCOMES {
+                let multiplier = Multiplier::whole(check_point_sicbo(&bet, sicbo_dices(outcome)));
+                let winnings = &mut round.outcome_winnings[outcome as usize];
+                *winnings = add_balance(*winnings, multiplier.apply(balance.0));
+            }
+            total = add_balance(total, balance.0);
+            let max_bet = self.sicbo_max_bet(&bet);
+            let amount = table_bet.bets.entry(bet).or_insert(0);
+            *amount = add_balance(*amount, balance.0);
+            assert!(*amount <= max_bet, "ERR_BET_ABOVE_MAX");
+        }
+        round.total_stake = add_balance(round.total_stake, total);
+        let best_case = round.outcome_winnings.iter().max().cloned().unwrap_or(0);
+
+        let mut account = self.users.get(&account_id).expect("Account Id does not exist");
+        let bonus_stake = self.collect_stake(&mut account, total);
+        self.users.insert(&account_id, &account);
+        table_bet.bonus_stake = add_balance(table_bet.bonus_stake, bonus_stake);
+        self.assert_exposure(best_case, round.total_stake);
+        let reserved = (0..SICBO_OUTCOMES)
+            .map(|outcome| table_winning(&table_bet.bets, |bet| check_point_sicbo(bet, sicbo_dices(outcome))))
+            .max()
+            .unwrap_or(0);
+        self.reserve_house(reserved - table_bet.reserved);
+        table_bet.reserved = reserved;
+
+        self.sicbo_table_bets.insert(&bet_key, &table_bet);
+        self.sicbo_table_rounds.insert(&round.id, &round);
+        round
+    }
+
+    // Rolls three independent dice on the first call and settles up to `limit` participants per call
+    pub fn roll_sicbo_table(&mut self, round_id: u64, limit: u64) -> SicBoRound {
+        let mut round = self.sicbo_table_rounds.get(&round_id).expect("ERR_ROUND_NOT_FOUND");
+        assert!(env::block_index() > round.betting_closes, "ERR_ROUND_NOT_CLOSED");
+        assert!(round.settled_participants < round.participants, "ERR_ROUND_ALREADY_SETTLED");
+
+        let dices = match round.dices.clone() {
+            Some(dices) => dices,
+            None => {
+                let dices = self.roll_dices();
+                round.dices = Some(dices.clone());
+                emit_event("sicbo_table_roll", vec![SicBoRoll { round_id, dices: dices.clone() }]);
+                dices
+            }
+        };
+
+        let end = round.settled_participants.saturating_add(limit).min(round.participants);
+        let mut settlements = Vec::new();
+        for index in round.settled_participants..end {
+            let account_id = self.sicbo_table_participants.get(&participant_key(round_id, index)).unwrap();
+            let bet_key = table_bet_key(round_id, &account_id);
+            let mut table_bet = self.sicbo_table_bets.get(&bet_key).unwrap();
+
+            let total_stake = table_bet.bets.values().fold(0, |total, amount| add_balance(total, *amount));
+            let total_winning = table_winning(&table_bet.bets, |bet| check_point_sicbo(bet, dices.clone()));
+
+            let mut account = self.users.get(&account_id).expect("Account Id does not exist");
+            self.release_house(table_bet.reserved);
+            self.pay_winning(&mut account, "sicbo_table", total_stake, table_bet.bonus_stake, total_winning, table_bet.placed_at.0, false);
+            self.users.insert(&account_id, &account);
+            self.check_sicbo_achievements(&account_id, table_bet.bets.keys(), &dices);
+
+            table_bet.total_winning = Some(total_winning);
+            self.sicbo_table_bets.insert(&bet_key, &table_bet);
+            round.total_winning = add_balance(round.total_winning, total_winning);
+            settlements.push(TableSettlement { round_id, account_id, total_stake, total_winning });
+        }
+        emit_event("sicbo_table_settle", settlements);
+
+        round.settled_participants = end;
+        self.sicbo_table_rounds.insert(&round_id, &round);
+        round
+    }
+
+    pub fn get_sicbo_table_round(&self, round_id: u64) -> Option<SicBoRound> {
+        self.sicbo_table_rounds.get(&round_id)
+    }
+
+    pub fn get_sicbo_table_round_id(&self) -> u64 {
+        self.sicbo_table_round_id
+    }
+
+    pub fn get_sicbo_table_participants(&self, round_id: u64, from_index: u64, limit: u64) -> Vec<AccountId> {
+        let round = self.sicbo_table_rounds.get(&round_id).expect("ERR_ROUND_NOT_FOUND");
+        (from_index..round.participants.min(from_index.saturating_add(limit)))
+            .map(|index| self.sicbo_table_participants.get(&participant_key(round_id, index)).unwrap())
+            .collect()
+    }
+
+    pub fn get_sicbo_table_bet(&self, round_id: u64, account_id: ValidAccountId) -> Option<SicBoTableBet> {
+        self.sicbo_table_bets.get(&table_bet_key(round_id, account_id.as_ref()))
+    }
+}
+
+impl Contract {
+    fn open_sicbo_table_round(&mut self) -> SicBoRound {
+        let block_index = env::block_index();
+        if let Some(round) = self.sicbo_table_rounds.get(&self.sicbo_table_round_id) {
+            if block_index <= round.betting_closes {
+                return round;
+            }
+        }
+
+        self.sicbo_table_round_id += 1;
+        SicBoRound {
+            id: self.sicbo_table_round_id,
+            betting_closes: block_index + self.table_blocks,
+            participants: 0,
+            dices: None,
+            settled_participants: 0,
+            total_stake: 0,
+            total_winning: 0,
+            outcome_winnings: vec![0; SICBO_OUTCOMES as usize],
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{bets, get_context, set_context};
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+
+    fn setup(house: Balance) -> Contract {
+        testing_env!(get_context(vec![], false));
+        let mut contract = Contract { house, ..Default::default() };
+        set_context("carol_near", 0, 0, 1000);
+        contract.deposit(None);
+        set_context("dave_near", 0, 0, 1000);
+        contract.deposit(None);
+        contract
+    }
+
+    #[test]
+    fn one_roll_settles_every_participant() {
+        let mut contract = setup(100_000);
+        contract.place_sicbo_table_bet(bets(&[("big", 100)]));
+        set_context("carol_near", 10, 0, 0);
+        let round = contract.place_sicbo_table_bet(bets(&[("small", 100), ("sum_10", 10)]));
+        assert_eq!(2, round.participants);
+        assert_eq!(vec!["dave_near".to_string()], contract.get_sicbo_table_participants(1, 0, 1));
+        assert_eq!(210, round.total_stake);
+
+        set_context("carol_near", 31, 0, 0);
+        contract.roll_sicbo_table(1, 1);
+        let round = contract.roll_sicbo_table(1, 1);
+        assert_eq!(2, round.settled_participants);
+
+        let dices = round.dices.unwrap();
+        for (account_id, placed) in [("dave_near", vec![("big", 100)]), ("carol_near", vec![("small", 100), ("sum_10", 10)])].iter() {
+            let total_winning: Balance = placed
+                .iter()
+                .map(|(bet, amount)| Multiplier::whole(check_point_sicbo(bet, dices.clone())).apply(*amount))
+                .sum();
+            let staked: Balance = placed.iter().map(|(_, amount)| amount).sum();
+            assert_eq!(1000 - staked + total_winning, contract.users.get(&account_id.to_string()).unwrap().deposit);
+        }
+    }
+
+    #[test]
+    fn exposure_is_shared_across_participants() {
+        // Two different doubles never land together, so the worst roll pays only one of them
+        let mut contract = setup(5_000);
+        contract.place_sicbo_table_bet(bets(&[("double_1", 50)]));
+        set_context("carol_near", 1, 0, 0);
+        let round = contract.place_sicbo_table_bet(bets(&[("double_6", 50)]));
+        assert_eq!(500, round.outcome_winnings.iter().max().cloned().unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_EXPOSURE_TOO_HIGH")]
+    fn round_exposure_is_capped() {
+        // Each bet alone stays under the cap, together they exceed it
+        let mut contract = setup(5_000);
+        contract.place_sicbo_table_bet(bets(&[("double_1", 50)]));
+        set_context("carol_near", 1, 0, 0);
+        contract.place_sicbo_table_bet(bets(&[("double_1", 50)]));
+    }
+}
@@ -0,0 +1,386 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{U128, U64};
+use near_sdk::{env, near_bindgen, AccountId, Balance};
+use serde::{Serialize, Deserialize};
+
+use crate::crash::participant_key;
+use crate::events::emit_event;
+use crate::payout::BASIS_POINTS;
+use crate::*;
+
+// Rounds placed before `ends_at` still score when they settle later, e.g. a crash round that
+// outlives the tournament, so finalizing waits this long past the end (in nanoseconds)
+pub const TOURNAMENT_SETTLEMENT_GRACE: u64 = 3_600_000_000_000;
+// Finalizing ranks every participant in one call, so the field is capped to keep it within gas
+pub const MAX_TOURNAMENT_PARTICIPANTS: u64 = 100;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TournamentMetric {
+    NetProfit,
+    Wagered,
+    BiggestMultiplier,
+}
+
+// An empty game list counts every game. `prize_bps` splits the pool between the top places.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+pub struct TournamentConfig {
+    name: String,
+    games: Vec<String>,
+    metric: TournamentMetric,
+    entry_fee: U128,
+    starts_at: U64,
+    ends_at: U64,
+    prize_bps: Vec<u32>,
+    max_participants: u64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+pub struct TournamentPrize {
+    account_id: AccountId,
+    score: i128,
+    prize: Balance,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+pub struct Tournament {
+    id: u64,
+    config: TournamentConfig,
+    participants: u64,
+    prize_pool: Balance,
+    prizes: Option<Vec<TournamentPrize>>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    account_id: AccountId,
+    score: i128,
+}
+
+#[near_bindgen]
+impl Contract {
+    // The owner can seed the prize pool out of their share of the house, entry fees are added to it
+    pub fn create_tournament(&mut self, config: TournamentConfig, seed: U128) -> Tournament {
+        self.assert_owner();
+        assert!(config.starts_at.0 < config.ends_at.0, "ERR_TOURNAMENT_PERIOD_NOT_VALID");
+        assert!(config.ends_at.0 > env::block_timestamp(), "ERR_TOURNAMENT_PERIOD_NOT_VALID");
+        assert!(!config.prize_bps.is_empty(), "ERR_PRIZES_NOT_VALID");
+        assert_eq!(
+            BASIS_POINTS,
+            config.prize_bps.iter().map(|bps| *bps as u128).sum::<u128>(),
+            "ERR_PRIZES_NOT_VALID"
+        );
+        assert!(
+            (1..=MAX_TOURNAMENT_PARTICIPANTS).contains(&config.max_participants),
+            "ERR_MAX_PARTICIPANTS_NOT_VALID"
+        );
+        self.spend_house(seed.0);
+
+        self.tournament_next_id += 1;
+        let tournament = Tournament {
+            id: self.tournament_next_id,
+            config,
+            participants: 0,
+            prize_pool: seed.0,
+            prizes: None,
+        };
+        self.tournaments.insert(&tournament.id, &tournament);
+        tournament
+    }
+
+    pub fn enter_tournament(&mut self, tournament_id: u64) -> Tournament {
+        let account_id = env::predecessor_account_id();
+        let mut tournament = self.tournaments.get(&tournament_id).expect("ERR_TOURNAMENT_NOT_FOUND");
+        assert!(env::block_timestamp() < tournament.config.ends_at.0, "ERR_TOURNAMENT_ENDED");
+        let score_key = tournament_score_key(tournament_id, &account_id);
+        assert!(self.tournament_scores.get(&score_key).is_none(), "ERR_ALREADY_ENTERED");
+        assert!(tournament.participants < tournament.config.max_participants, "ERR_TOURNAMENT_FULL");
+        self.assert_not_excluded(&account_id);
+
+        let entry_fee = tournament.config.entry_fee.0;
+        let mut account = self.users.get(&account_id).expect("Account Id does not exist");
+        assert!(entry_fee <= account.deposit, "Account deposit does not sufficient for the bet");
+        account.deposit -= entry_fee;
+        self.users.insert(&account_id, &account);
+        tournament.prize_pool = add_balance(tournament.prize_pool, entry_fee);
+
+        self.tournament_participants.insert(&participant_key(tournament_id, tournament.participants), &account_id);
+        tournament.participants += 1;
+        self.tournament_scores.insert(&score_key, &0);
+        self.tournaments.insert(&tournament_id, &tournament);
+        let mut entered = self.account_tournaments.get(&account_id).unwrap_or_default();
+        entered.push(tournament_id);
+        self.account_tournaments.insert(&account_id, &entered);
+        tournament
+    }
+
+    // Anyone can finalize once the tournament has ended and the settlement grace has passed.
+    // Unawarded places and rounding go back to the house.
+    pub fn finalize_tournament(&mut self, tournament_id: u64) -> Tournament {
+        let mut tournament = self.tournaments.get(&tournament_id).expect("ERR_TOURNAMENT_NOT_FOUND");
+        let closes_at = tournament.config.ends_at.0.saturating_add(TOURNAMENT_SETTLEMENT_GRACE);
+        assert!(env::block_timestamp() >= closes_at, "ERR_TOURNAMENT_NOT_ENDED");
+        assert!(tournament.prizes.is_none(), "ERR_TOURNAMENT_FINALIZED");
+
+        let mut paid: Balance = 0;
+        let mut prizes = Vec::new();
+        let leaderboard = self.leaderboard(&tournament);
+        for (entry, bps) in leaderboard.into_iter().zip(tournament.config.prize_bps.iter()) {
+            let prize = tournament.prize_pool * *bps as u128 / BASIS_POINTS;
+            let mut account = self.users.get(&entry.account_id).expect("Account Id does not exist");
+            account.deposit = add_balance(account.deposit, prize);
+            self.users.insert(&entry.account_id, &account);
+            paid = add_balance(paid, prize);
+            prizes.push(TournamentPrize { account_id: entry.account_id, score: entry.score, prize });
+        }
+        self.house = add_balance(self.house, tournament.prize_pool - paid);
+
+        emit_event("tournament_finalized", prizes.clone());
+        tournament.prizes = Some(prizes);
+        self.tournaments.insert(&tournament_id, &tournament);
+        tournament
+    }
+
+    pub fn get_tournament(&self, tournament_id: u64) -> Option<Tournament> {
+        self.tournaments.get(&tournament_id)
+    }
+
+    pub fn get_tournament_leaderboard(&self, tournament_id: u64, limit: u64) -> Vec<LeaderboardEntry> {
+        let tournament = self.tournaments.get(&tournament_id).expect("ERR_TOURNAMENT_NOT_FOUND");
+        self.leaderboard(&tournament).into_iter().take(limit as usize).collect()
+    }
+
+    pub fn get_tournament_participants(&self, tournament_id: u64, from_index: u64, limit: u64) -> Vec<AccountId> {
+        let tournament = self.tournaments.get(&tournament_id).expect("ERR_TOURNAMENT_NOT_FOUND");
+        (from_index..tournament.participants.min(from_index.saturating_add(limit)))
+            .map(|index| self.tournament_participants.get(&participant_key(tournament_id, index)).unwrap())
+            .collect()
+    }
+}
+
+impl Contract {
+    // Ties keep the order in which players entered
+    fn leaderboard(&self, tournament: &Tournament) -> Vec<LeaderboardEntry> {
+        let mut leaderboard: Vec<LeaderboardEntry> = (0..tournament.participants)
+            .map(|index| {
+                let account_id = self.tournament_participants.get(&participant_key(tournament.id, index)).unwrap();
+                let score = self.tournament_scores.get(&tournament_score_key(tournament.id, &account_id)).unwrap_or(0);
+                LeaderboardEntry { account_id, score }
+            })
+            .collect();
+        leaderboard.sort_by_key(|entry| std::cmp::Reverse(entry.score));
+        leaderboard
+    }
+
+    // Updates every running tournament the account entered, and forgets the ones that have ended
+    // Plays count by the time the bet was placed, so a round settled after the end still scores
+    // until the tournament is finalized
+    pub(crate) fn record_tournament_play(
+        &mut self,
+        account_id: &AccountId,
+        game: &str,
+        stake: Balance,
+        total_winning: Balance,
+        placed_at: u64,
+    ) {
+        let entered = match self.account_tournaments.get(account_id) {
+            Some(entered) => entered,
+            None => return,
+        };
+        let mut running = Vec::new();
+        for tournament_id in entered {
+            let tournament = self.tournaments.get(&tournament_id).unwrap();
+            if tournament.prizes.is_some() {
+                continue;
+            }
+            running.push(tournament_id);
+            let config = tournament.config;
+            if placed_at < config.starts_at.0 || placed_at >= config.ends_at.0 {
+                continue;
+            }
+            if !(config.games.is_empty() || config.games.iter().any(|name| name == game)) {
+                continue;
+            }
+
+            let score_key = tournament_score_key(tournament_id, account_id);
+            let score = self.tournament_scores.get(&score_key).unwrap_or(0);
+            let score = match config.metric {
+                TournamentMetric::NetProfit => score + total_winning as i128 - stake as i128,
+                TournamentMetric::Wagered => score + stake as i128,
+                TournamentMetric::BiggestMultiplier if stake > 0 => {
+                    score.max((total_winning * BASIS_POINTS / stake) as i128)
+                }
+                TournamentMetric::BiggestMultiplier => score,
+            };
+            self.tournament_scores.insert(&score_key, &score);
+        }
+
+        if running.is_empty() {
+            self.account_tournaments.remove(account_id);
+        } else {
+            self.account_tournaments.insert(account_id, &running);
+        }
+    }
+}
+
+fn tournament_score_key(tournament_id: u64, account_id: &str) -> String {
+    format!("{}:{}", tournament_id, account_id)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{get_context, set_context};
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+
+    fn setup(metric: TournamentMetric, games: Vec<String>) -> Contract {
+        testing_env!(get_context(vec![], false));
+        let mut contract = Contract { owner_id: "alice_near".to_string(), house: 100_000, ..Default::default() };
+        for account_id in ["carol_near", "dave_near", "erin_near"].iter() {
+            set_context(account_id, 0, 0, 1000);
+            contract.deposit(None);
+        }
+        set_context("alice_near", 0, 0, 0);
+        let config = TournamentConfig {
+            name: "weekly".to_string(),
+            games,
+            metric,
+            entry_fee: U128(100),
+            starts_at: U64(10),
+            ends_at: U64(100),
+            prize_bps: vec![7_000, 3_000],
+            max_participants: 10,
+        };
+        contract.create_tournament(config, U128(1_000));
+        contract
+    }
+
+    fn play(contract: &mut Contract, account_id: &str, game: &str, stake: Balance, total_winning: Balance) {
+        let mut account = contract.users.get(&account_id.to_string()).unwrap();
+        let bonus_stake = contract.collect_stake(&mut account, stake);
+        contract.pay_winning(&mut account, game, stake, bonus_stake, total_winning, env::block_timestamp(), false);
+        contract.users.insert(&account.account_id, &account);
+    }
+
+    #[test]
+    fn prizes_follow_the_leaderboard() {
+        let mut contract = setup(TournamentMetric::NetProfit, vec![]);
+        for account_id in ["carol_near", "dave_near", "erin_near"].iter() {
+            set_context(account_id, 0, 5, 0);
+            contract.enter_tournament(1);
+        }
+        // Plays before the start do not count
+        play(&mut contract, "erin_near", "dice", 100, 500);
+
+        set_context("carol_near", 0, 50, 0);
+        play(&mut contract, "carol_near", "dice", 100, 300);
+        play(&mut contract, "dave_near", "keno", 100, 0);
+        play(&mut contract, "erin_near", "crash", 100, 150);
+
+        let leaderboard = contract.get_tournament_leaderboard(1, 10);
+        let ranking: Vec<(&str, i128)> = leaderboard.iter().map(|entry| (entry.account_id.as_str(), entry.score)).collect();
+        assert_eq!(vec![("carol_near", 200), ("erin_near", 50), ("dave_near", -100)], ranking);
+
+        set_context("dave_near", 0, 100 + TOURNAMENT_SETTLEMENT_GRACE, 0);
+        let house = contract.house;
+        let tournament = contract.finalize_tournament(1);
+        assert_eq!(1_300, tournament.prize_pool);
+        let prizes = tournament.prizes.unwrap();
+        assert_eq!(910, prizes[0].prize);
+        assert_eq!(390, prizes[1].prize);
+        assert_eq!(1000 - 100 + 200 + 910, contract.users.get(&"carol_near".to_string()).unwrap().deposit);
+        assert_eq!(house, contract.house);
+        assert!(contract.account_tournaments.get(&"carol_near".to_string()).is_some());
+        play(&mut contract, "carol_near", "dice", 100, 0);
+        assert!(contract.account_tournaments.get(&"carol_near".to_string()).is_none());
+    }
+
+    #[test]
+    fn game_filter_and_multiplier_metric() {
+        let mut contract = setup(TournamentMetric::BiggestMultiplier, vec!["plinko".to_string()]);
+        set_context("carol_near", 0, 20, 0);
+        contract.enter_tournament(1);
+        play(&mut contract, "carol_near", "dice", 100, 900);
+        play(&mut contract, "carol_near", "plinko", 100, 250);
+        play(&mut contract, "carol_near", "plinko", 100, 120);
+        assert_eq!(25_000, contract.get_tournament_leaderboard(1, 1)[0].score);
+    }
+
+    #[test]
+    fn rounds_placed_before_the_end_score_when_settled_late() {
+        let mut contract = setup(TournamentMetric::Wagered, vec![]);
+        set_context("carol_near", 0, 20, 0);
+        contract.enter_tournament(1);
+        let mut account = contract.users.get(&"carol_near".to_string()).unwrap();
+        let bonus_stake = contract.collect_stake(&mut account, 100);
+
+        set_context("carol_near", 0, 150, 0);
+        contract.pay_winning(&mut account, "crash", 100, bonus_stake, 0, 90, false);
+        // A round placed after the end does not count, even before finalizing
+        contract.pay_winning(&mut account, "dice", 100, 0, 0, 150, false);
+        assert_eq!(100, contract.get_tournament_leaderboard(1, 1)[0].score);
+    }
+
+    #[test]
+    fn void_rounds_are_not_wagered() {
+        let mut contract = setup(TournamentMetric::Wagered, vec![]);
+        set_context("carol_near", 0, 20, 0);
+        contract.enter_tournament(1);
+        let mut account = contract.users.get(&"carol_near".to_string()).unwrap();
+        let bonus_stake = contract.collect_stake(&mut account, 100);
+        contract.pay_winning(&mut account, "hilo", 100, bonus_stake, 100, env::block_timestamp(), true);
+        assert_eq!(0, contract.get_tournament_leaderboard(1, 1)[0].score);
+        // A round that was played and happens to return its stake still counts
+        play(&mut contract, "carol_near", "hilo", 100, 100);
+        assert_eq!(100, contract.get_tournament_leaderboard(1, 1)[0].score);
+    }
+
+    #[test]
+    fn entries_are_stored_outside_the_tournament() {
+        let mut contract = setup(TournamentMetric::Wagered, vec![]);
+        for account_id in ["carol_near", "dave_near"].iter() {
+            set_context(account_id, 0, 5, 0);
+            contract.enter_tournament(1);
+        }
+        assert_eq!(2, contract.get_tournament(1).unwrap().participants);
+        assert_eq!(vec!["dave_near".to_string()], contract.get_tournament_participants(1, 1, 10));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ALREADY_ENTERED")]
+    fn cannot_enter_twice() {
+        let mut contract = setup(TournamentMetric::Wagered, vec![]);
+        set_context("carol_near", 0, 5, 0);
+        contract.enter_tournament(1);
+        contract.enter_tournament(1);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MAX_PARTICIPANTS_NOT_VALID")]
+    fn field_size_is_bounded() {
+        testing_env!(get_context(vec![], false));
+        set_context("alice_near", 0, 0, 0);
+        let mut contract = Contract { owner_id: "alice_near".to_string(), house: 100_000, ..Default::default() };
+        let config = TournamentConfig {
+            name: "open".to_string(),
+            games: vec![],
+            metric: TournamentMetric::Wagered,
+            entry_fee: U128(0),
+            starts_at: U64(10),
+            ends_at: U64(100),
+            prize_bps: vec![10_000],
+            max_participants: MAX_TOURNAMENT_PARTICIPANTS + 1,
+        };
+        contract.create_tournament(config, U128(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_TOURNAMENT_NOT_ENDED")]
+    fn cannot_finalize_early() {
+        let mut contract = setup(TournamentMetric::Wagered, vec![]);
+        set_context("carol_near", 0, 100 + TOURNAMENT_SETTLEMENT_GRACE - 1, 0);
+        contract.finalize_tournament(1);
+    }
+}
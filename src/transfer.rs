@@ -0,0 +1,89 @@
+use near_sdk::json_types::{U128, ValidAccountId};
+use near_sdk::{env, near_bindgen, AccountId, Balance};
+use serde::Serialize;
+
+use crate::events::emit_event;
+use crate::*;
+
+#[derive(Serialize)]
+struct BalanceTransfer {
+    sender_id: AccountId,
+    receiver_id: AccountId,
+    amount: Balance,
+    memo: Option<String>,
+}
+
+#[near_bindgen]
+impl Contract {
+    // Moves cash between two registered accounts without leaving the contract. The receiver's
+    // deposit limits and self-exclusion apply, since the funds can be played straight away.
+    pub fn transfer_balance(&mut self, receiver: ValidAccountId, amount: U128, memo: Option<String>) -> Balance {
+        assert!(amount.0 > 0, "ERR_ZERO_TRANSFER");
+        let sender_id = env::predecessor_account_id();
+        let receiver_id: AccountId = receiver.into();
+        assert_ne!(sender_id, receiver_id, "ERR_SELF_TRANSFER");
+        let mut sender = self.users.get(&sender_id).expect("Account Id does not exist");
+        let mut receiver = self.users.get(&receiver_id).expect("ERR_RECEIVER_NOT_REGISTERED");
+        // Bonus funds stay locked until they are wagered, as for withdrawals
+        assert!(amount.0 <= sender.deposit, "ERR_TRANSFER_EXCEEDS_CASH");
+        self.check_deposit_limits(&receiver_id, amount.0);
+
+        sender.deposit -= amount.0;
+        receiver.deposit = add_balance(receiver.deposit, amount.0);
+        self.users.insert(&sender_id, &sender);
+        self.users.insert(&receiver_id, &receiver);
+        emit_event("balance_transfer", vec![BalanceTransfer { sender_id, receiver_id, amount: amount.0, memo }]);
+        sender.deposit
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{get_context, set_context};
+    use near_sdk::test_utils::get_logs;
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+    use std::convert::TryInto;
+
+    fn setup() -> Contract {
+        testing_env!(get_context(vec![], false));
+        let mut contract = Contract::default();
+        set_context("dave_near", 0, 0, 500);
+        contract.deposit(None);
+        set_context("carol_near", 0, 0, 1000);
+        contract.deposit(None);
+        contract
+    }
+
+    #[test]
+    fn transfer_moves_cash_and_emits_an_event() {
+        let mut contract = setup();
+        set_context("carol_near", 0, 0, 0);
+        assert_eq!(750, contract.transfer_balance("dave_near".try_into().unwrap(), U128(250), Some("gg".to_string())));
+        assert_eq!(750, contract.users.get(&"dave_near".to_string()).unwrap().deposit);
+        assert_eq!(
+            vec![
+                "EVENT_JSON:{\"standard\":\"sicbo\",\"version\":\"1.0.0\",\"event\":\"balance_transfer\",\"data\":[{\"sender_id\":\"carol_near\",\"receiver_id\":\"dave_near\",\"amount\":250,\"memo\":\"gg\"}]}"
+            ],
+            get_logs()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_RECEIVER_NOT_REGISTERED")]
+    fn receiver_must_be_registered() {
+        let mut contract = setup();
+        set_context("carol_near", 0, 0, 0);
+        contract.transfer_balance("erin_near".try_into().unwrap(), U128(250), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_TRANSFER_EXCEEDS_CASH")]
+    fn only_cash_can_be_transferred() {
+        let mut contract = setup();
+        set_context("carol_near", 0, 0, 0);
+        contract.transfer_balance("dave_near".try_into().unwrap(), U128(1001), None);
+    }
+}
@@ -0,0 +1,186 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{U128, ValidAccountId};
+use near_sdk::{env, near_bindgen, Balance};
+use serde::{Serialize, Deserialize};
+
+use crate::payout::BASIS_POINTS;
+use crate::*;
+
+const ONE_NEAR: Balance = 1_000_000_000_000_000_000_000_000;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+pub struct VipTier {
+    name: String,
+    min_wagered: U128,
+    rakeback_bps: u32,
+}
+
+// Rakeback is a share of the game's expected house edge on every stake, the same edge the
+// referral commission is taken from, so it never exceeds what the house expects to win
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+pub struct VipConfig {
+    tiers: Vec<VipTier>,
+}
+
+impl Default for VipConfig {
+    fn default() -> Self {
+        let tier = |name: &str, min_near: u128, rakeback_bps| VipTier {
+            name: name.to_string(),
+            min_wagered: U128(min_near * ONE_NEAR),
+            rakeback_bps,
+        };
+        Self {
+            tiers: vec![
+                tier("bronze", 0, 0),
+                tier("silver", 1_000, 500),
+                tier("gold", 10_000, 1_000),
+                tier("platinum", 100_000, 1_500),
+            ],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct VipStatus {
+    tier: u8,
+    name: String,
+    rakeback_bps: u32,
+    wagered: U128,
+    next_tier_wagered: Option<U128>,
+    claimable_rakeback: U128,
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn get_vip_status(&self, account_id: ValidAccountId) -> VipStatus {
+        let account = self.get_account(account_id);
+        let tier = self.vip_tier(account.wagered);
+        let current = &self.vip_config.tiers[tier];
+        VipStatus {
+            tier: tier as u8,
+            name: current.name.clone(),
+            rakeback_bps: current.rakeback_bps,
+            wagered: U128(account.wagered),
+            next_tier_wagered: self.vip_config.tiers.get(tier + 1).map(|next| next.min_wagered),
+            claimable_rakeback: U128(account.rakeback),
+        }
+    }
+
+    pub fn claim_rakeback(&mut self) -> Balance {
+        let account_id = env::predecessor_account_id();
+        let mut account = self.users.get(&account_id).expect("Account Id does not exist");
+        assert!(account.rakeback > 0, "ERR_NO_RAKEBACK");
+        account.deposit = add_balance(account.deposit, account.rakeback);
+        account.rakeback = 0;
+        self.users.insert(&account_id, &account);
+        account.deposit
+    }
+
+    pub fn get_vip_config(&self) -> VipConfig {
+        self.vip_config.clone()
+    }
+
+    // Tiers are ordered by threshold and the first one starts at zero, so every account has a tier
+    pub fn set_vip_config(&mut self, config: VipConfig) {
+        self.assert_owner();
+        assert_eq!(Some(0), config.tiers.first().map(|tier| tier.min_wagered.0), "ERR_VIP_TIERS_NOT_VALID");
+        assert!(
+            config.tiers.windows(2).all(|pair| pair[0].min_wagered.0 < pair[1].min_wagered.0),
+            "ERR_VIP_TIERS_NOT_VALID"
+        );
+        assert!(
+            config.tiers.iter().all(|tier| tier.rakeback_bps as u128 <= BASIS_POINTS),
+            "ERR_RAKEBACK_NOT_VALID"
+        );
+        self.vip_config = config;
+    }
+}
+
+impl Contract {
+    fn vip_tier(&self, wagered: Balance) -> usize {
+        self.vip_config.tiers.iter().rposition(|tier| tier.min_wagered.0 <= wagered).unwrap_or(0)
+    }
+
+    // Counts the stake towards the account's volume, then pays rakeback at the resulting tier
+    pub(crate) fn accrue_rakeback(&mut self, account: &mut Account, game: &str, stake: Balance) {
+        let previous_tier = self.vip_tier(account.wagered);
+        account.wagered = add_balance(account.wagered, stake);
+        let tier = self.vip_tier(account.wagered);
+        if tier > previous_tier {
+            self.award_achievement(&account.account_id, Achievement::VipTier(tier as u8));
+        }
+        let rakeback_bps = self.vip_config.tiers[tier].rakeback_bps as u128;
+        let rakeback = stake * self.min_house_edge_bps(game) / BASIS_POINTS * rakeback_bps / BASIS_POINTS;
+        if rakeback == 0 {
+            return;
+        }
+        self.house = self.house.checked_sub(rakeback).expect("ERR_HOUSE_BALANCE_NOT_SUFFICIENT");
+        account.rakeback = add_balance(account.rakeback, rakeback);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::get_context;
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+    use std::convert::TryInto;
+
+    fn setup() -> Contract {
+        let mut context = get_context(vec![], false);
+        context.attached_deposit = 100_000 * ONE_NEAR;
+        testing_env!(context);
+        let mut contract = Contract { house: 1_000_000 * ONE_NEAR, ..Default::default() };
+        contract.deposit(None);
+        contract
+    }
+
+    #[test]
+    fn volume_moves_the_account_up_the_tiers() {
+        let mut contract = setup();
+        let mut account = contract.users.get(&"carol_near".to_string()).unwrap();
+        contract.settle(&mut account, "dice", 999 * ONE_NEAR, 0);
+        let status = contract.get_vip_status("carol_near".try_into().unwrap());
+        assert_eq!(0, status.tier);
+        assert_eq!(0, status.claimable_rakeback.0);
+        assert_eq!(Some(U128(1_000 * ONE_NEAR)), status.next_tier_wagered);
+
+        // Silver pays 5% of the 1.5% dice edge, including on the bet that reaches the tier
+        let mut account = contract.users.get(&"carol_near".to_string()).unwrap();
+        contract.settle(&mut account, "dice", 1_000 * ONE_NEAR, 2_000 * ONE_NEAR);
+        let status = contract.get_vip_status("carol_near".try_into().unwrap());
+        assert_eq!("silver", status.name);
+        assert_eq!(1_999 * ONE_NEAR, status.wagered.0);
+        assert_eq!(3 * ONE_NEAR / 4, status.claimable_rakeback.0);
+
+        assert_eq!(100_000 * ONE_NEAR - 999 * ONE_NEAR + 1_000 * ONE_NEAR + 3 * ONE_NEAR / 4, contract.claim_rakeback());
+    }
+
+    #[test]
+    fn rounds_closed_at_one_times_earn_nothing() {
+        let mut contract = setup();
+        let mut account = contract.users.get(&"carol_near".to_string()).unwrap();
+        let bonus_stake = contract.collect_stake(&mut account, 1_000 * ONE_NEAR);
+        contract.pay_winning(&mut account, "mines", 1_000 * ONE_NEAR, bonus_stake, 1_000 * ONE_NEAR, env::block_timestamp(), true);
+        contract.users.insert(&account.account_id, &account);
+        assert_eq!(0, contract.get_vip_status("carol_near".try_into().unwrap()).wagered.0);
+
+        // A mines game that was played and happens to return its stake still counts
+        let bonus_stake = contract.collect_stake(&mut account, 1_000 * ONE_NEAR);
+        contract.pay_winning(&mut account, "mines", 1_000 * ONE_NEAR, bonus_stake, 1_000 * ONE_NEAR, env::block_timestamp(), false);
+        contract.users.insert(&account.account_id, &account);
+        assert_eq!(1_000 * ONE_NEAR, contract.get_vip_status("carol_near".try_into().unwrap()).wagered.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_VIP_TIERS_NOT_VALID")]
+    fn tiers_must_be_ordered() {
+        testing_env!(get_context(vec![], false));
+        let mut contract = Contract { owner_id: "carol_near".to_string(), ..Default::default() };
+        let mut config = VipConfig::default();
+        config.tiers.swap(1, 2);
+        contract.set_vip_config(config);
+    }
+}
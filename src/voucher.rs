@@ -0,0 +1,195 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::{U128, ValidAccountId};
+use near_sdk::{env, near_bindgen, AccountId, Balance, BlockHeight};
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+use crate::*;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum VoucherGame {
+    Sicbo,
+    Roulette,
+    Dice,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum VoucherCredit {
+    Cash,
+    Bonus,
+}
+
+// Dice bets are keyed like `roll_under_5000`, the same keys `preview_dice` reports
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone)]
+pub struct Voucher {
+    id: u64,
+    game: VoucherGame,
+    stake: Balance,
+    expires_at: BlockHeight,
+    bet_keys: Option<Vec<Bet>>,
+}
+
+#[near_bindgen]
+impl Contract {
+    pub fn issue_voucher(
+        &mut self,
+        account_id: ValidAccountId,
+        game: VoucherGame,
+        stake: U128,
+        expires_at: BlockHeight,
+        bet_keys: Option<Vec<Bet>>,
+    ) -> Voucher {
+        self.assert_owner();
+        assert!(stake.0 > 0, "ERR_ZERO_BET");
+        assert!(expires_at >= env::block_index(), "ERR_VOUCHER_EXPIRED");
+        for bet in bet_keys.iter().flatten() {
+            match game {
+                VoucherGame::Sicbo => { check_point_sicbo(bet, vec![1, 1, 1]); }
+                VoucherGame::Roulette => { check_point_roulette(bet, 0); }
+                VoucherGame::Dice => {
+                    let (mode, target) = parse_dice_bet(bet);
+                    dice_winning_outcomes(mode, target);
+                }
+            }
+        }
+        let account_id: AccountId = account_id.into();
+        assert!(self.users.get(&account_id).is_some(), "Account Id does not exist");
+
+        self.voucher_next_id += 1;
+        let voucher = Voucher { id: self.voucher_next_id, game, stake: stake.0, expires_at, bet_keys };
+        let mut vouchers = self.vouchers.get(&account_id).unwrap_or_default();
+        vouchers.push(voucher.clone());
+        self.vouchers.insert(&account_id, &vouchers);
+        voucher
+    }
+
+    pub fn get_vouchers(&self, account_id: ValidAccountId) -> Vec<Voucher> {
+        self.vouchers.get(account_id.as_ref()).unwrap_or_default()
+    }
+
+    pub fn get_voucher_credit(&self) -> VoucherCredit {
+        self.voucher_credit
+    }
+
+    pub fn set_voucher_credit(&mut self, voucher_credit: VoucherCredit) {
+        self.assert_owner();
+        self.voucher_credit = voucher_credit;
+    }
+}
+
+impl Contract {
+    // Consumes the voucher if the bets spend exactly its stake on allowed keys of its game
+    pub(crate) fn redeem_voucher(&mut self, account_id: &AccountId, voucher_id: u64, game: VoucherGame, bets: &HashMap<Bet, U128>) {
+        self.assert_not_excluded(account_id);
+        let mut vouchers = self.vouchers.get(account_id).unwrap_or_default();
+        let index = vouchers.iter().position(|voucher| voucher.id == voucher_id).expect("ERR_VOUCHER_NOT_FOUND");
+        let voucher = vouchers.remove(index);
+        assert_eq!(voucher.game, game, "ERR_VOUCHER_GAME_MISMATCH");
+        assert!(env::block_index() <= voucher.expires_at, "ERR_VOUCHER_EXPIRED");
+        let total = bets.values().fold(0, |total, amount| add_balance(total, amount.0));
+        assert_eq!(voucher.stake, total, "ERR_VOUCHER_STAKE_MISMATCH");
+        if let Some(bet_keys) = &voucher.bet_keys {
+            assert!(bets.keys().all(|bet| bet_keys.contains(bet)), "ERR_VOUCHER_BET_NOT_ALLOWED");
+        }
+
+        if vouchers.is_empty() {
+            self.vouchers.remove(account_id);
+        } else {
+            self.vouchers.insert(account_id, &vouchers);
+        }
+    }
+
+    // The stake was never the player's, so the bet neither counts as volume nor touches the jackpot.
+    // The winnings are a promotion, paid out of the owner's share of the house.
+    pub(crate) fn pay_voucher(&mut self, account: &mut Account, total_winning: Balance) {
+        self.spend_house(total_winning);
+        match self.voucher_credit {
+            VoucherCredit::Cash => account.deposit = add_balance(account.deposit, total_winning),
+            VoucherCredit::Bonus => self.add_bonus(account, total_winning),
+        }
+        self.users.insert(&account.account_id, account);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{bets, get_context, set_context};
+    use near_sdk::MockedBlockchain;
+    use near_sdk::testing_env;
+    use std::convert::TryInto;
+
+    fn setup(voucher_credit: VoucherCredit) -> Contract {
+        let mut context = get_context(vec![], false);
+        context.attached_deposit = 1000;
+        context.predecessor_account_id = "alice_near".to_string();
+        testing_env!(context);
+        let mut contract = Contract { house: 100_000, voucher_credit, ..Default::default() };
+        contract.deposit(None);
+        contract
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DICE_CHANCE_NOT_VALID")]
+    fn dice_keys_are_validated_at_issue() {
+        let mut contract = setup(VoucherCredit::Cash);
+        let bet_keys = vec!["roll_under_5000".to_string(), "roll_over_9990".to_string()];
+        contract.issue_voucher("alice_near".try_into().unwrap(), VoucherGame::Dice, U128(100), 10, Some(bet_keys));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_UNKNOWN_BET")]
+    fn malformed_dice_keys_are_rejected() {
+        let mut contract = setup(VoucherCredit::Cash);
+        let bet_keys = vec!["roll_sideways_5000".to_string()];
+        contract.issue_voucher("alice_near".try_into().unwrap(), VoucherGame::Dice, U128(100), 10, Some(bet_keys));
+    }
+
+    #[test]
+    fn free_bet_pays_without_spending_the_deposit() {
+        let mut contract = setup(VoucherCredit::Cash);
+        let voucher = contract.issue_voucher("alice_near".try_into().unwrap(), VoucherGame::Dice, U128(100), 10, None);
+
+        // A zero seed rolls 30.36
+        let result = contract.play_dice(DiceMode::RollUnder, 5000, U128(100), Some(voucher.id));
+        assert_eq!(197, result.total_winning);
+        let account = contract.users.get(&"alice_near".to_string()).unwrap();
+        assert_eq!(1197, account.deposit);
+        assert_eq!(0, account.wagered);
+        assert_eq!(100_000 - 197, contract.house);
+        assert!(contract.get_vouchers("alice_near".try_into().unwrap()).is_empty());
+    }
+
+    #[test]
+    fn winnings_can_be_credited_as_bonus() {
+        let mut contract = setup(VoucherCredit::Bonus);
+        let voucher = contract.issue_voucher("alice_near".try_into().unwrap(), VoucherGame::Sicbo, U128(100), 10, None);
+
+        // A zero seed rolls 5-2-2
+        let result = contract.play_sicbo(bets(&[("small", 100)]), Some(voucher.id));
+        assert_eq!(100, result.total_winning);
+        let account = contract.users.get(&"alice_near".to_string()).unwrap();
+        assert_eq!((1000, 100, 2000), (account.deposit, account.bonus, account.bonus_wagering));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_VOUCHER_BET_NOT_ALLOWED")]
+    fn restricted_keys_are_enforced() {
+        let mut contract = setup(VoucherCredit::Cash);
+        let keys = Some(vec!["red".to_string(), "black".to_string()]);
+        let voucher = contract.issue_voucher("alice_near".try_into().unwrap(), VoucherGame::Roulette, U128(100), 10, keys);
+        contract.play_roulette(bets(&[("red", 50), ("0", 50)]), Some(voucher.id));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_VOUCHER_EXPIRED")]
+    fn expired_voucher_is_rejected() {
+        let mut contract = setup(VoucherCredit::Cash);
+        let voucher = contract.issue_voucher("alice_near".try_into().unwrap(), VoucherGame::Roulette, U128(100), 10, None);
+        set_context("alice_near", 11, 0, 0);
+        contract.play_roulette(bets(&[("red", 100)]), Some(voucher.id));
+    }
+}